@@ -0,0 +1,55 @@
+use crate::error::{Error, Result};
+use crate::project_log::Grouping;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+const BIN_NAME: &str = env!("CARGO_PKG_NAME");
+const FILE_NAME: &str = "config.toml";
+
+/// Settings of the user, read from `config.toml` in the config folder of the OS
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Project to log to when none is given
+    pub default_project: Option<String>,
+    /// Description of logs without one
+    pub default_description: Option<String>,
+    /// Folder to keep the projects in instead of the data folder of the OS
+    pub data_dir: Option<PathBuf>,
+    /// Expected hours per weekday for new projects, in the same format as `init --schedule`
+    pub schedule: Option<String>,
+    pub output: OutputConfig,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// How to summarize reports when not given
+    pub report: Option<Grouping>,
+}
+
+impl Config {
+    /// Reads the config file, or gives the default config if there is none
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::io(&path, err)),
+        };
+        toml::from_str(&content).map_err(|err| Error::Corrupt {
+            path,
+            reason: err.message().to_owned(),
+        })
+    }
+
+    fn path() -> Option<PathBuf> {
+        let mut path = dirs::config_dir()?;
+        path.push(BIN_NAME);
+        path.push(FILE_NAME);
+        Some(path)
+    }
+}
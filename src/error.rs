@@ -0,0 +1,121 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidDate(String),
+    InvalidTime(String),
+    InvalidPeriod(String),
+    /// Any other input which does not make sense
+    InvalidInput(String),
+    ProjectNotFound(String),
+    ProjectExists(String),
+    LogNotFound(usize),
+    /// The command can not be run in the current state of the project
+    InvalidState(String),
+    /// The logs look wrong, such as overlapping others, and were not added
+    Conflict(Vec<String>),
+    NoDataFolder,
+    Corrupt {
+        path: PathBuf,
+        reason: String,
+    },
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Database {
+        path: PathBuf,
+        source: rusqlite::Error,
+    },
+}
+
+impl Error {
+    /// Exit code of the program when failing with the error, where 2 is shared
+    /// with clap for badly formed commands.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::InvalidDate(_)
+            | Error::InvalidTime(_)
+            | Error::InvalidPeriod(_)
+            | Error::InvalidInput(_) => 2,
+            Error::ProjectNotFound(_) | Error::LogNotFound(_) => 3,
+            Error::ProjectExists(_) => 4,
+            Error::InvalidState(_) => 5,
+            Error::Corrupt { .. } => 6,
+            Error::NoDataFolder | Error::Io { .. } | Error::Database { .. } => 7,
+            Error::Conflict(_) => 8,
+        }
+    }
+
+    pub fn io(path: &std::path::Path, source: std::io::Error) -> Self {
+        Error::Io {
+            path: path.to_owned(),
+            source,
+        }
+    }
+
+    pub fn database(path: &std::path::Path, source: rusqlite::Error) -> Self {
+        Error::Database {
+            path: path.to_owned(),
+            source,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::InvalidDate(date) => {
+                write!(
+                    f,
+                    "Invalid date '{}', expected [[YYYY-]MM-]DD, today, yesterday, -N, \
+                     [last|next] <weekday> or YYYY-Www[-D]",
+                    date
+                )
+            }
+            Error::InvalidTime(time) => {
+                write!(f, "Invalid time '{}', expected hours[:minutes]", time)
+            }
+            Error::InvalidPeriod(period) => write!(
+                f,
+                "Invalid period '{}', expected hours[:minutes]-hours[:minutes][+days]",
+                period
+            ),
+            Error::InvalidInput(msg) => write!(f, "{}", msg),
+            Error::ProjectNotFound(project) => write!(
+                f,
+                "Project '{}' does not exist, initialize it with `init` first",
+                project
+            ),
+            Error::ProjectExists(project) => write!(f, "Project '{}' already exists", project),
+            Error::LogNotFound(id) => write!(f, "There is no log with id {} in the project", id),
+            Error::InvalidState(msg) => write!(f, "{}", msg),
+            Error::Conflict(problems) => {
+                write!(f, "Nothing was saved since")?;
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                write!(f, "\nUse --force to save it anyway")
+            }
+            Error::NoDataFolder => write!(f, "Could not find a data folder to save logs in"),
+            Error::Corrupt { path, reason } => {
+                write!(f, "Could not read {}: {}", path.display(), reason)
+            }
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Database { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Database { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use crate::error::{Error, Result};
+use crate::parser::parse_iso_date;
+use crate::time::Date;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+
+/// Countries with a built-in holiday calendar
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Country {
+    Sweden,
+    Norway,
+    Finland,
+    Germany,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Holiday {
+    date: Date,
+    name: String,
+}
+
+/// The days off for a project, from a built-in calendar and/or imported from files
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HolidayCalendar {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    country: Option<Country>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    imported: Vec<Holiday>,
+}
+
+/// How to find the date of a holiday in a given year
+enum Rule {
+    Fixed {
+        month: usize,
+        day: usize,
+    },
+    /// Days after easter sunday (negative for before)
+    Easter(i64),
+    /// The first weekday (0 is monday) on or after a date
+    WeekdayFrom {
+        weekday: usize,
+        month: usize,
+        day: usize,
+    },
+}
+
+const SWEDEN: &[(&str, Rule)] = &[
+    ("New Year's Day", Rule::Fixed { month: 1, day: 1 }),
+    ("Epiphany", Rule::Fixed { month: 1, day: 6 }),
+    ("Good Friday", Rule::Easter(-2)),
+    ("Easter Monday", Rule::Easter(1)),
+    ("May Day", Rule::Fixed { month: 5, day: 1 }),
+    ("Ascension Day", Rule::Easter(39)),
+    ("National Day", Rule::Fixed { month: 6, day: 6 }),
+    (
+        "Midsummer Eve",
+        Rule::WeekdayFrom {
+            weekday: 4,
+            month: 6,
+            day: 19,
+        },
+    ),
+    (
+        "Midsummer Day",
+        Rule::WeekdayFrom {
+            weekday: 5,
+            month: 6,
+            day: 20,
+        },
+    ),
+    (
+        "All Saints' Day",
+        Rule::WeekdayFrom {
+            weekday: 5,
+            month: 10,
+            day: 31,
+        },
+    ),
+    ("Christmas Eve", Rule::Fixed { month: 12, day: 24 }),
+    ("Christmas Day", Rule::Fixed { month: 12, day: 25 }),
+    ("Boxing Day", Rule::Fixed { month: 12, day: 26 }),
+    ("New Year's Eve", Rule::Fixed { month: 12, day: 31 }),
+];
+
+const NORWAY: &[(&str, Rule)] = &[
+    ("New Year's Day", Rule::Fixed { month: 1, day: 1 }),
+    ("Maundy Thursday", Rule::Easter(-3)),
+    ("Good Friday", Rule::Easter(-2)),
+    ("Easter Monday", Rule::Easter(1)),
+    ("Labour Day", Rule::Fixed { month: 5, day: 1 }),
+    ("Constitution Day", Rule::Fixed { month: 5, day: 17 }),
+    ("Ascension Day", Rule::Easter(39)),
+    ("Whit Monday", Rule::Easter(50)),
+    ("Christmas Day", Rule::Fixed { month: 12, day: 25 }),
+    ("Boxing Day", Rule::Fixed { month: 12, day: 26 }),
+];
+
+const FINLAND: &[(&str, Rule)] = &[
+    ("New Year's Day", Rule::Fixed { month: 1, day: 1 }),
+    ("Epiphany", Rule::Fixed { month: 1, day: 6 }),
+    ("Good Friday", Rule::Easter(-2)),
+    ("Easter Monday", Rule::Easter(1)),
+    ("May Day", Rule::Fixed { month: 5, day: 1 }),
+    ("Ascension Day", Rule::Easter(39)),
+    (
+        "Midsummer Eve",
+        Rule::WeekdayFrom {
+            weekday: 4,
+            month: 6,
+            day: 19,
+        },
+    ),
+    (
+        "Midsummer Day",
+        Rule::WeekdayFrom {
+            weekday: 5,
+            month: 6,
+            day: 20,
+        },
+    ),
+    (
+        "All Saints' Day",
+        Rule::WeekdayFrom {
+            weekday: 5,
+            month: 10,
+            day: 31,
+        },
+    ),
+    ("Independence Day", Rule::Fixed { month: 12, day: 6 }),
+    ("Christmas Eve", Rule::Fixed { month: 12, day: 24 }),
+    ("Christmas Day", Rule::Fixed { month: 12, day: 25 }),
+    ("Boxing Day", Rule::Fixed { month: 12, day: 26 }),
+];
+
+// Only the holidays common to all states
+const GERMANY: &[(&str, Rule)] = &[
+    ("New Year's Day", Rule::Fixed { month: 1, day: 1 }),
+    ("Good Friday", Rule::Easter(-2)),
+    ("Easter Monday", Rule::Easter(1)),
+    ("Labour Day", Rule::Fixed { month: 5, day: 1 }),
+    ("Ascension Day", Rule::Easter(39)),
+    ("Whit Monday", Rule::Easter(50)),
+    ("German Unity Day", Rule::Fixed { month: 10, day: 3 }),
+    ("Christmas Day", Rule::Fixed { month: 12, day: 25 }),
+    ("Boxing Day", Rule::Fixed { month: 12, day: 26 }),
+];
+
+impl Country {
+    fn rules(&self) -> &'static [(&'static str, Rule)] {
+        match self {
+            Country::Sweden => SWEDEN,
+            Country::Norway => NORWAY,
+            Country::Finland => FINLAND,
+            Country::Germany => GERMANY,
+        }
+    }
+
+    pub fn holidays(&self, year: usize) -> Vec<Holiday> {
+        self.rules()
+            .iter()
+            .map(|(name, rule)| Holiday::new(rule.date(year), name.to_string()))
+            .collect()
+    }
+}
+
+impl Rule {
+    fn date(&self, year: usize) -> Date {
+        match self {
+            Rule::Fixed { month, day } => Date::new(year, *month, *day),
+            Rule::Easter(offset) => easter_sunday(year).add_days(*offset),
+            Rule::WeekdayFrom {
+                weekday,
+                month,
+                day,
+            } => {
+                let from = Date::new(year, *month, *day);
+                from.add_days(((weekday + 7 - from.weekday()) % 7) as i64)
+            }
+        }
+    }
+}
+
+/// Computus using the anonymous gregorian algorithm
+fn easter_sunday(year: usize) -> Date {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b + 1 - f) / 3;
+    let h = (19 * a + b + 15 - d - g) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l + 114 - 7 * m) / 31;
+    let day = (h + l + 114 - 7 * m) % 31 + 1;
+
+    Date::new(year, month, day)
+}
+
+impl Holiday {
+    pub fn new(date: Date, name: String) -> Self {
+        Self { date, name }
+    }
+
+    pub fn date(&self) -> &Date {
+        &self.date
+    }
+}
+
+impl HolidayCalendar {
+    pub fn set_country(&mut self, country: Option<Country>) {
+        self.country = country;
+    }
+
+    /// Adds holidays to the imported ones, returning how many were new
+    pub fn import(&mut self, holidays: Vec<Holiday>) -> usize {
+        let before = self.imported.len();
+        for holiday in holidays {
+            if !self.imported.contains(&holiday) {
+                self.imported.push(holiday);
+            }
+        }
+        self.imported.sort_by(|a, b| a.date.cmp(&b.date));
+        self.imported.len() - before
+    }
+
+    pub fn clear_imported(&mut self) {
+        self.imported.clear();
+    }
+
+    /// All holidays from one date until another, both inclusive, one per date
+    pub fn between(&self, from: &Date, to: &Date) -> Vec<Holiday> {
+        let (from_year, _, _) = from.into_ymd();
+        let (to_year, _, _) = to.into_ymd();
+
+        let built_in = self
+            .country
+            .iter()
+            .flat_map(|country| (from_year..=to_year).flat_map(|year| country.holidays(year)));
+
+        let mut by_date = BTreeMap::new();
+        for holiday in built_in.chain(self.imported.iter().cloned()) {
+            if &holiday.date >= from && &holiday.date <= to {
+                by_date.entry(holiday.date.clone()).or_insert(holiday);
+            }
+        }
+        by_date.into_values().collect()
+    }
+}
+
+impl Display for Holiday {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.date, self.name)
+    }
+}
+
+impl Display for HolidayCalendar {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.country {
+            Some(country) => write!(f, "Built-in calendar: {:?}", country)?,
+            None => write!(f, "No built-in calendar")?,
+        }
+        write!(f, ", {} imported holidays", self.imported.len())
+    }
+}
+
+/// Reads holidays from either an iCalendar file or a plain text file with one
+/// `YYYY-MM-DD [name]` per line.
+pub fn read_holiday_file(path: &Path) -> Result<Vec<Holiday>> {
+    let content = std::fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+
+    if content.trim_start().starts_with("BEGIN:VCALENDAR") {
+        parse_icalendar(&content)
+    } else {
+        parse_plain_text(&content)
+    }
+}
+
+fn parse_plain_text(content: &str) -> Result<Vec<Holiday>> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(nbr, line)| (nbr, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(nbr, line)| {
+            let (date_str, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let date = parse_iso_date(date_str).map_err(|_| {
+                Error::InvalidInput(format!("Invalid date on line {}: {}", nbr + 1, line))
+            })?;
+            Ok(Holiday::new(date, name_or_default(name.trim())))
+        })
+        .collect()
+}
+
+fn parse_icalendar(content: &str) -> Result<Vec<Holiday>> {
+    // Lines starting with whitespace continue the previous one
+    let unfolded = content
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+
+    let mut holidays = vec![];
+    let mut event: Option<(Option<Date>, Option<Date>, String)> = None;
+
+    for line in unfolded.lines() {
+        let Some((property, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = property.split(';').next().unwrap_or(property);
+
+        match (name, &mut event) {
+            ("BEGIN", None) if value == "VEVENT" => event = Some((None, None, String::new())),
+            ("DTSTART", Some((start, _, _))) => *start = parse_ical_date(value),
+            ("DTEND", Some((_, end, _))) => *end = parse_ical_date(value),
+            ("SUMMARY", Some((_, _, summary))) => {
+                *summary = value.replace("\\,", ",").replace("\\;", ";")
+            }
+            ("END", Some(_)) if value == "VEVENT" => {
+                let (start, end, summary) = event.take().unwrap();
+                let start = start.ok_or_else(|| {
+                    Error::InvalidInput(format!(
+                        "Calendar event '{}' has no valid start date",
+                        summary
+                    ))
+                })?;
+                // All day events have an exclusive end date
+                let last = match end {
+                    Some(end) if end > start => end.add_days(-1),
+                    _ => start.clone(),
+                };
+                for date in last.dates_since(&start) {
+                    holidays.push(Holiday::new(date, name_or_default(&summary)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(holidays)
+}
+
+/// Parses the date of a DTSTART or DTEND, ignoring any time after it
+fn parse_ical_date(value: &str) -> Option<Date> {
+    let digits = value
+        .get(..8)
+        .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))?;
+    parse_iso_date(&format!(
+        "{}-{}-{}",
+        &digits[..4],
+        &digits[4..6],
+        &digits[6..]
+    ))
+    .ok()
+}
+
+fn name_or_default(name: &str) -> String {
+    if name.is_empty() {
+        "Holiday".to_owned()
+    } else {
+        name.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easter_sunday_of_known_years() {
+        for (year, month, day) in [
+            (1961, 4, 2),
+            (2000, 4, 23),
+            (2008, 3, 23),
+            (2011, 4, 24),
+            (2019, 4, 21),
+            (2024, 3, 31),
+            (2025, 4, 20),
+            (2038, 4, 25),
+            (2285, 3, 22),
+        ] {
+            assert_eq!(easter_sunday(year), Date::new(year, month, day), "{}", year);
+        }
+    }
+
+    #[test]
+    fn icalendar_events() {
+        let calendar = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20231224\r\n\
+            DTEND;VALUE=DATE:20231227\r\nSUMMARY:Christmas\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nDTSTART:20240101T000000Z\r\nSUMMARY:New year\\, again\r\n\
+            END:VEVENT\r\nEND:VCALENDAR\r\n";
+        let holidays = parse_icalendar(calendar).unwrap();
+        let dates: Vec<_> = holidays
+            .iter()
+            .map(|holiday| holiday.date.clone())
+            .collect();
+        assert_eq!(
+            dates,
+            [
+                Date::new(2023, 12, 24),
+                Date::new(2023, 12, 25),
+                Date::new(2023, 12, 26),
+                Date::new(2024, 1, 1)
+            ]
+        );
+        assert_eq!(holidays[3].name, "New year, again");
+    }
+
+    #[test]
+    fn icalendar_dates_which_are_not_digits() {
+        for start in ["123é5678", "2023-12-24", "2023122", "20231332"] {
+            let calendar = format!("BEGIN:VEVENT\nDTSTART:{}\nEND:VEVENT\n", start);
+            assert!(
+                matches!(parse_icalendar(&calendar), Err(Error::InvalidInput(_))),
+                "{}",
+                start
+            );
+        }
+    }
+}
@@ -1,36 +1,129 @@
+mod config;
+mod error;
+mod holidays;
 pub mod parser;
 mod project_log;
 pub mod time;
 
+use error::Result;
 use parser::CliResult;
 use project_log::{ProjectLog, WorkLog};
 
 fn main() {
-    match parser::parse() {
+    if let Err(error) = run() {
+        eprintln!("Error: {}", error);
+        std::process::exit(error.exit_code());
+    }
+}
+
+fn run() -> Result<()> {
+    match parser::parse()? {
         CliResult::PeriodLog {
             project,
-            period,
+            periods,
             date,
             desc,
             breaks,
+            timezone,
+            force,
         } => {
-            let work_log = WorkLog::new_period(period, date, desc, breaks);
-            ProjectLog::log(&project, work_log);
+            let work_logs = periods
+                .into_iter()
+                .map(|period| {
+                    WorkLog::new_period(
+                        period,
+                        date.clone(),
+                        desc.clone(),
+                        breaks.clone(),
+                        timezone.clone(),
+                    )
+                })
+                .collect();
+            ProjectLog::log(&project, work_logs, force)
         }
         CliResult::SimpleLog {
             project,
             duration,
             date,
             desc,
+            force,
         } => {
             let work_log = WorkLog::new_duration(duration, date, desc);
-            ProjectLog::log(&project, work_log);
+            ProjectLog::log(&project, vec![work_log], force)
         }
+        CliResult::Absence {
+            project,
+            kind,
+            from,
+            to,
+            half_day,
+            desc,
+        } => ProjectLog::absence(&project, kind, from, to, half_day, desc),
         CliResult::Init {
             project,
             start_date,
-        } => ProjectLog::init(project, start_date),
+            schedule,
+        } => ProjectLog::init(project, start_date, schedule),
+        CliResult::Schedule {
+            project,
+            schedule,
+            max_per_day,
+        } => ProjectLog::schedule(&project, schedule, max_per_day),
         CliResult::Delete { project, date } => ProjectLog::delete(&project, date),
         CliResult::Wipe { project } => ProjectLog::wipe(&project),
+        CliResult::Undo { project, count } => ProjectLog::undo(&project, count),
+        CliResult::History { project, count } => ProjectLog::history(&project, count),
+        CliResult::Holidays {
+            project,
+            country,
+            import,
+            clear,
+            year,
+        } => ProjectLog::holidays(&project, country, import, clear, year),
+        CliResult::Vacation {
+            project,
+            days_per_year,
+            accrual,
+            max_carry_over,
+        } => ProjectLog::vacation(&project, days_per_year, accrual, max_carry_over),
+        CliResult::List { project, from, to } => ProjectLog::list(&project, from, to),
+        CliResult::Edit {
+            project,
+            id,
+            edit,
+            force,
+        } => ProjectLog::edit(&project, id, edit, force),
+        CliResult::Remove { project, id } => ProjectLog::remove(&project, id),
+        CliResult::Report {
+            project,
+            grouping,
+            from,
+            to,
+        } => ProjectLog::report(&project, grouping, from, to),
+        CliResult::Start { project } => ProjectLog::start(&project),
+        CliResult::Stop {
+            project,
+            desc,
+            breaks,
+            force,
+        } => ProjectLog::stop(&project, desc, breaks, force),
+        CliResult::Pause { project } => ProjectLog::pause(&project),
+        CliResult::Resume { project } => ProjectLog::resume(&project),
+        CliResult::Projects { all } => ProjectLog::projects(all),
+        CliResult::Rename { project, new_name } => ProjectLog::rename(&project, &new_name),
+        CliResult::Archive { project, restore } => ProjectLog::archive(&project, restore),
+        CliResult::End { project, date } => ProjectLog::end(&project, date),
+        CliResult::MigrateStorage { kind } => ProjectLog::migrate_storage(kind),
+        CliResult::Import {
+            project,
+            logs,
+            force,
+        } => ProjectLog::import(&project, logs, force),
+        CliResult::Export {
+            project,
+            format,
+            from,
+            to,
+        } => ProjectLog::export(&project, format, from, to),
     }
 }
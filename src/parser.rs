@@ -1,27 +1,57 @@
-use crate::time::{now, Date, Duration, Period, Time};
-use clap::{Parser, Subcommand};
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::holidays::{read_holiday_file, Country, Holiday};
+use crate::project_log::{
+    self, read_import_file, Absence, Accrual, Break, ExportFormat, Field, Grouping, ImportFormat,
+    LogEdit, ProjectLog, Schedule, StorageKind, WorkLog,
+};
+use crate::time::{now, Date, Duration, Period, Time, TimeZone, WEEKDAYS};
+use clap::{Parser, Subcommand, ValueEnum};
 use core::str::FromStr;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::fmt::Debug;
+use std::path::PathBuf;
+
+/// Longest period which is assumed to pass midnight without saying so
+const MAX_OVERNIGHT_HOURS: i32 = 12;
+
+/// Most midnights a period can pass, which keeps its duration from overflowing
+const MAX_PERIOD_DAYS: usize = 366;
 
 pub enum CliResult {
     PeriodLog {
         project: String,
-        period: Period,
+        periods: Vec<Period>,
         date: Date,
         desc: String,
-        breaks: Vec<Duration>,
+        breaks: Vec<Break>,
+        timezone: Option<TimeZone>,
+        force: bool,
     },
     SimpleLog {
         project: String,
         duration: Duration,
         date: Date,
         desc: String,
+        force: bool,
+    },
+    Absence {
+        project: String,
+        kind: Absence,
+        from: Date,
+        to: Date,
+        half_day: bool,
+        desc: Option<String>,
     },
     Init {
         project: String,
         start_date: Date,
+        schedule: Option<Schedule>,
+    },
+    Schedule {
+        project: String,
+        schedule: Option<Schedule>,
+        max_per_day: Option<Duration>,
     },
     Delete {
         project: String,
@@ -30,6 +60,92 @@ pub enum CliResult {
     Wipe {
         project: String,
     },
+    Undo {
+        project: String,
+        count: usize,
+    },
+    History {
+        project: String,
+        count: usize,
+    },
+    Holidays {
+        project: String,
+        country: Option<Option<Country>>,
+        import: Option<Vec<Holiday>>,
+        clear: bool,
+        year: usize,
+    },
+    Vacation {
+        project: String,
+        days_per_year: Option<f32>,
+        accrual: Option<Accrual>,
+        max_carry_over: Option<f32>,
+    },
+    List {
+        project: String,
+        from: Option<Date>,
+        to: Option<Date>,
+    },
+    Edit {
+        project: String,
+        id: usize,
+        edit: LogEdit,
+        force: bool,
+    },
+    Remove {
+        project: String,
+        id: usize,
+    },
+    Report {
+        project: String,
+        grouping: Grouping,
+        from: Option<Date>,
+        to: Option<Date>,
+    },
+    Start {
+        project: String,
+    },
+    Stop {
+        project: String,
+        desc: String,
+        breaks: Vec<Break>,
+        force: bool,
+    },
+    Pause {
+        project: String,
+    },
+    Resume {
+        project: String,
+    },
+    Projects {
+        all: bool,
+    },
+    Rename {
+        project: String,
+        new_name: String,
+    },
+    Archive {
+        project: String,
+        restore: bool,
+    },
+    End {
+        project: String,
+        date: Option<Date>,
+    },
+    MigrateStorage {
+        kind: StorageKind,
+    },
+    Export {
+        project: String,
+        format: ExportFormat,
+        from: Option<Date>,
+        to: Option<Date>,
+    },
+    Import {
+        project: String,
+        logs: Vec<WorkLog>,
+        force: bool,
+    },
 }
 
 #[derive(Parser)]
@@ -37,14 +153,39 @@ pub enum CliResult {
 struct Cli {
     #[command(subcommand)]
     sub: SubCli,
+
+    /// Folder to keep the projects in, overriding the config and the data folder of the OS
+    #[arg(long, global = true, env = "FLEX_CLI_DATA_DIR")]
+    data_dir: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
 enum SubCli {
     Log(CliLog),
+    Absence(CliAbsence),
     Init(CliInit),
+    Schedule(CliSchedule),
     Delete(CliDelete),
     Wipe(CliWipe),
+    Undo(CliUndo),
+    History(CliHistory),
+    Holidays(CliHolidays),
+    Vacation(CliVacation),
+    List(CliList),
+    Edit(CliEdit),
+    Rm(CliRm),
+    Report(CliReport),
+    Start(CliStart),
+    Stop(CliStop),
+    Pause(CliPause),
+    Resume(CliResume),
+    Projects(CliProjects),
+    Rename(CliRename),
+    Archive(CliArchive),
+    End(CliEnd),
+    MigrateStorage(CliMigrateStorage),
+    Export(CliExport),
+    Import(CliImport),
 }
 
 // TODO: Make one and only one of time and period be required. Use ArgGroup::multiple(true)
@@ -53,27 +194,60 @@ enum SubCli {
 /// Log a time or period at work
 #[derive(Parser)]
 pub struct CliLog {
-    /// Which project shall we log the time towards?
-    project: String, // TODO: Make default project accesable
+    /// Which project shall we log the time towards? (defaults to the one in the config)
+    project: Option<String>,
 
-    /// What did you do today?
-    description: String,
+    /// What did you do today? (defaults to the one in the config)
+    description: Option<String>,
 
     /// Time spent at work (hours[:minutes])
     #[arg(short, long)]
     time: Option<String>,
 
-    /// Period spent at work (hours[:minutes]-hours[:minutes])
-    #[arg(short, long)]
-    period: Option<String>,
+    /// Periods spent at work (hours[:minutes]-hours[:minutes][+days]), repeated or comma separated
+    #[arg(short, long, value_delimiter = ',')]
+    period: Vec<String>,
 
     /// Which date you want to log it for
-    #[arg(short, long)]
+    #[arg(short, long, allow_hyphen_values = true)]
     date: Option<String>,
 
     /// Breaks not counted in work (hours[:minutes])*
     #[arg(short, long)]
     breaks: Vec<String>,
+
+    /// Timezone of the period if not the local one, such as Europe/Stockholm or UTC+2
+    #[arg(long, allow_hyphen_values = true)]
+    tz: Option<String>,
+
+    /// Log even if it overlaps other logs or looks wrong in some other way
+    #[arg(long)]
+    force: bool,
+}
+
+/// Log a day away from work, such as vacation or sick leave
+#[derive(Parser)]
+struct CliAbsence {
+    /// Project name
+    name: String,
+
+    /// What kind of absence it is
+    kind: Absence,
+
+    /// Optional note about the absence
+    description: Option<String>,
+
+    /// Which date you are away (the first one if used with --to)
+    #[arg(short, long, allow_hyphen_values = true)]
+    date: Option<String>,
+
+    /// Last date of a longer absence, logging all workdays in between
+    #[arg(long, allow_hyphen_values = true)]
+    to: Option<String>,
+
+    /// Only away for half the day
+    #[arg(long)]
+    half: bool,
 }
 
 /// Initialize a project for logging
@@ -83,7 +257,26 @@ pub struct CliInit {
     name: String,
 
     /// Starting date
+    #[arg(allow_hyphen_values = true)]
     date: Option<String>,
+
+    /// Expected hours per weekday, starting on monday (hours[:minutes],...)
+    #[arg(short, long)]
+    schedule: Option<String>,
+}
+
+/// Show or change the expected working hours of each weekday
+#[derive(Parser)]
+struct CliSchedule {
+    /// Project name
+    name: String,
+
+    /// Expected hours per weekday, starting on monday (hours[:minutes],...)
+    schedule: Option<String>,
+
+    /// Most hours which can be logged in a day to this project without forcing it (hours[:minutes])
+    #[arg(short, long)]
+    max_day: Option<String>,
 }
 
 /// Delete all logs for a specific day
@@ -93,6 +286,7 @@ struct CliDelete {
     name: String,
 
     /// Date to delete all logs for
+    #[arg(allow_hyphen_values = true)]
     date: Option<String>,
 }
 
@@ -103,120 +297,914 @@ struct CliWipe {
     name: String,
 }
 
-pub fn parse() -> CliResult {
+/// Revert the latest changes to a project, of which at least the latest 100 are kept
+#[derive(Parser)]
+struct CliUndo {
+    /// Project name
+    name: String,
+
+    /// How many changes to revert
+    #[arg(default_value_t = 1)]
+    count: usize,
+}
+
+/// Show the latest changes made to a project
+#[derive(Parser)]
+struct CliHistory {
+    /// Project name
+    name: String,
+
+    /// How many changes to show
+    #[arg(default_value_t = 10)]
+    count: usize,
+}
+
+/// Show or change which holidays are not expected to be worked
+#[derive(Parser)]
+struct CliHolidays {
+    /// Project name
+    name: String,
+
+    /// Use the built-in holiday calendar of a country
+    #[arg(short, long, conflicts_with = "no_country")]
+    country: Option<Country>,
+
+    /// Stop using a built-in holiday calendar
+    #[arg(long)]
+    no_country: bool,
+
+    /// Import holidays from an iCalendar file or a text file with one "YYYY-MM-DD [name]" per line
+    #[arg(short, long)]
+    import: Option<PathBuf>,
+
+    /// Remove all previously imported holidays
+    #[arg(long)]
+    clear: bool,
+
+    /// Which year to list the holidays for
+    #[arg(short, long)]
+    year: Option<usize>,
+}
+
+/// Show the vacation balance, or change how many vacation days are given
+#[derive(Parser)]
+struct CliVacation {
+    /// Project name
+    name: String,
+
+    /// Vacation days given per year, counted from the project start date
+    #[arg(long)]
+    days: Option<f32>,
+
+    /// Whether the days are given all at once or month by month
+    #[arg(long)]
+    accrual: Option<Accrual>,
+
+    /// Max unused days that can be saved to the next year
+    #[arg(long)]
+    carry_over: Option<f32>,
+}
+
+/// List logged entries along with their ids
+#[derive(Parser)]
+struct CliList {
+    /// Project name
+    name: String,
+
+    /// Only list the logs of this date
+    #[arg(short, long, conflicts_with_all = ["from", "to"], allow_hyphen_values = true)]
+    date: Option<String>,
+
+    /// First date to list logs from
+    #[arg(short, long, allow_hyphen_values = true)]
+    from: Option<String>,
+
+    /// Last date to list logs from
+    #[arg(short, long, allow_hyphen_values = true)]
+    to: Option<String>,
+}
+
+/// Change parts of a logged entry
+#[derive(Parser)]
+struct CliEdit {
+    /// Project name
+    name: String,
+
+    /// Id of the log, as shown by list
+    id: usize,
+
+    /// New time spent at work (hours[:minutes])
+    #[arg(short, long, conflicts_with = "period")]
+    time: Option<String>,
+
+    /// New period spent at work (hours[:minutes]-hours[:minutes][+days])
+    #[arg(short, long)]
+    period: Option<String>,
+
+    /// New date of the log
+    #[arg(short, long, allow_hyphen_values = true)]
+    date: Option<String>,
+
+    /// New breaks, replacing the old ones (hours[:minutes])*
+    #[arg(short, long, conflicts_with = "no_breaks")]
+    breaks: Vec<String>,
+
+    /// Remove all breaks
+    #[arg(long)]
+    no_breaks: bool,
+
+    /// New description
+    #[arg(short = 'm', long)]
+    description: Option<String>,
+
+    /// Save the change even if the log overlaps other logs or looks wrong in some other way
+    #[arg(long)]
+    force: bool,
+}
+
+/// Remove a single logged entry
+#[derive(Parser)]
+struct CliRm {
+    /// Project name
+    name: String,
+
+    /// Id of the log, as shown by list
+    id: usize,
+}
+
+/// Summarize worked and expected hours per day, week or month
+#[derive(Parser)]
+struct CliReport {
+    /// Project name
+    name: String,
+
+    /// Summarize per week instead of per day, or what the config says
+    #[arg(short, long, conflicts_with = "month")]
+    week: bool,
+
+    /// Summarize per month instead of per day
+    #[arg(short, long)]
+    month: bool,
+
+    /// First date of the report (defaults to the project start)
+    #[arg(short, long, allow_hyphen_values = true)]
+    from: Option<String>,
+
+    /// Last date of the report (defaults to today)
+    #[arg(short, long, allow_hyphen_values = true)]
+    to: Option<String>,
+}
+
+/// Start a work session (clock in) now
+#[derive(Parser)]
+struct CliStart {
+    /// Project name
+    name: String,
+}
+
+/// Stop the running work session (clock out) and log it
+#[derive(Parser)]
+struct CliStop {
+    /// Project name
+    name: String,
+
+    /// What did you do during the session?
+    description: String,
+
+    /// Breaks not counted in work (hours[:minutes])*
+    #[arg(short, long)]
+    breaks: Vec<String>,
+
+    /// Log even if it overlaps other logs or looks wrong in some other way
+    #[arg(long)]
+    force: bool,
+}
+
+/// Pause the running work session to take a break
+#[derive(Parser)]
+struct CliPause {
+    /// Project name
+    name: String,
+}
+
+/// Resume the paused work session, recording the break
+#[derive(Parser)]
+struct CliResume {
+    /// Project name
+    name: String,
+}
+
+/// List all projects with their flex time
+#[derive(Parser)]
+struct CliProjects {
+    /// Include archived projects
+    #[arg(short, long)]
+    all: bool,
+}
+
+/// Give a project a new name
+#[derive(Parser)]
+struct CliRename {
+    /// Project name
+    name: String,
+
+    /// New name of the project
+    new_name: String,
+}
+
+/// Hide a project from the list and stop logging to it
+#[derive(Parser)]
+struct CliArchive {
+    /// Project name
+    name: String,
+
+    /// Bring the project back from the archive
+    #[arg(short, long)]
+    restore: bool,
+}
+
+/// Stop expecting hours after a date, such as when a contract ends
+#[derive(Parser)]
+struct CliEnd {
+    /// Project name
+    name: String,
+
+    /// Last date any hours are expected (defaults to today)
+    #[arg(allow_hyphen_values = true)]
+    date: Option<String>,
+
+    /// Remove the end date, expecting hours again
+    #[arg(long, conflicts_with = "date")]
+    reopen: bool,
+}
+
+/// Move all projects to another kind of storage
+#[derive(Parser)]
+struct CliMigrateStorage {
+    /// Kind of storage to move to
+    #[arg(value_enum)]
+    kind: StorageKind,
+}
+
+/// Write the logs of a project in a format for other programs, such as a spreadsheet
+#[derive(Parser)]
+struct CliExport {
+    /// Project name
+    name: String,
+
+    /// Format to write the logs in
+    #[arg(long, value_enum, default_value = "csv")]
+    format: ExportFormat,
+
+    /// First date to export logs from
+    #[arg(short, long, allow_hyphen_values = true)]
+    from: Option<String>,
+
+    /// Last date to export logs from
+    #[arg(short, long, allow_hyphen_values = true)]
+    to: Option<String>,
+}
+
+/// Add logs exported from flex-cli or another time tracker, skipping those already logged
+#[derive(Parser)]
+struct CliImport {
+    /// Project name
+    name: String,
+
+    /// File to import
+    file: PathBuf,
+
+    /// Format of the file (defaults to json for .json files and csv otherwise)
+    #[arg(long, value_enum)]
+    format: Option<ImportFormat>,
+
+    /// Which column holds a field of the logs, as field=header, such as date=Day,duration=Hours.
+    /// Fields: date, start, end, end-date, breaks, duration and description
+    #[arg(short, long, value_delimiter = ',')]
+    column: Vec<String>,
+
+    /// Import even if the logs overlap other logs or look wrong in some other way
+    #[arg(long)]
+    force: bool,
+}
+
+/// Parses the command line before reading the config, so that --help and --version work
+/// even when the config is broken
+pub fn parse() -> Result<CliResult> {
     let cli = Cli::parse();
-    match cli.sub {
-        SubCli::Log(log) => parse_log(log),
-        SubCli::Init(init) => parse_init(init.name, init.date),
-        SubCli::Delete(delete) => parse_delete(delete.name, delete.date),
-        SubCli::Wipe(wipe) => CliResult::Wipe { project: wipe.name },
+    let config = &Config::load()?;
+    if let Some(data_dir) = cli.data_dir.as_ref().or(config.data_dir.as_ref()) {
+        project_log::set_data_dir(data_dir.clone());
     }
+
+    let result = match cli.sub {
+        SubCli::Log(log) => parse_log(log, config)?,
+        SubCli::Absence(absence) => parse_absence(absence)?,
+        SubCli::Init(init) => parse_init(
+            init.name,
+            init.date,
+            init.schedule.or_else(|| config.schedule.clone()),
+        )?,
+        SubCli::Schedule(schedule) => CliResult::Schedule {
+            project: schedule.name,
+            schedule: schedule
+                .schedule
+                .as_deref()
+                .map(parse_schedule)
+                .transpose()?,
+            max_per_day: schedule
+                .max_day
+                .as_deref()
+                .map(parse_duration)
+                .transpose()?,
+        },
+        SubCli::Delete(delete) => parse_delete(delete.name, delete.date)?,
+        SubCli::Wipe(wipe) => CliResult::Wipe { project: wipe.name },
+        SubCli::Undo(undo) => CliResult::Undo {
+            project: undo.name,
+            count: undo.count,
+        },
+        SubCli::History(history) => CliResult::History {
+            project: history.name,
+            count: history.count,
+        },
+        SubCli::Holidays(holidays) => parse_holidays(holidays)?,
+        SubCli::Vacation(vacation) => CliResult::Vacation {
+            project: vacation.name,
+            days_per_year: vacation.days,
+            accrual: vacation.accrual,
+            max_carry_over: vacation.carry_over,
+        },
+        SubCli::List(list) => parse_list(list)?,
+        SubCli::Edit(edit) => parse_edit(edit)?,
+        SubCli::Rm(rm) => CliResult::Remove {
+            project: rm.name,
+            id: rm.id,
+        },
+        SubCli::Report(report) => parse_report(report, config)?,
+        SubCli::Start(start) => CliResult::Start {
+            project: start.name,
+        },
+        SubCli::Stop(stop) => CliResult::Stop {
+            project: stop.name,
+            desc: stop.description,
+            breaks: parse_breaks(&stop.breaks)?,
+            force: stop.force,
+        },
+        SubCli::Pause(pause) => CliResult::Pause {
+            project: pause.name,
+        },
+        SubCli::Resume(resume) => CliResult::Resume {
+            project: resume.name,
+        },
+        SubCli::Projects(projects) => CliResult::Projects { all: projects.all },
+        SubCli::Rename(rename) => CliResult::Rename {
+            project: rename.name,
+            new_name: rename.new_name,
+        },
+        SubCli::Archive(archive) => CliResult::Archive {
+            project: archive.name,
+            restore: archive.restore,
+        },
+        SubCli::End(end) => CliResult::End {
+            project: end.name,
+            date: if end.reopen {
+                None
+            } else {
+                Some(end.date.map_or(Ok(now().0), parse_date)?)
+            },
+        },
+        SubCli::MigrateStorage(migrate) => CliResult::MigrateStorage { kind: migrate.kind },
+        SubCli::Import(import) => parse_import(import, config)?,
+        SubCli::Export(export) => CliResult::Export {
+            project: export.name,
+            format: export.format,
+            from: export.from.map(parse_date).transpose()?,
+            to: export.to.map(parse_date).transpose()?,
+        },
+    };
+    Ok(result)
 }
 
-fn parse_log(log: CliLog) -> CliResult {
-    let date = log.date.map_or(now().0, parse_date);
+/// Parses a log, where a single name is taken as the description if it is not a project
+/// and there is a default project
+fn parse_log(log: CliLog, config: &Config) -> Result<CliResult> {
+    let (project, description) = match (log.project, log.description, &config.default_project) {
+        (Some(project), Some(description), _) => (project, Some(description)),
+        (Some(name), None, Some(default)) if !ProjectLog::exists(&name)? => {
+            (default.clone(), Some(name))
+        }
+        (Some(project), None, _) => (project, None),
+        (None, _, Some(default)) => (default.clone(), None),
+        (None, _, None) => {
+            return Err(Error::InvalidInput(
+                "No project given, and there is no default project in the config".to_owned(),
+            ))
+        }
+    };
+    let description = description
+        .or_else(|| config.default_description.clone())
+        .unwrap_or("Work".to_owned());
+
+    let timezone = log
+        .tz
+        .as_deref()
+        .map(TimeZone::from_str)
+        .transpose()
+        .map_err(Error::InvalidInput)?;
+    let today = timezone.as_ref().map_or_else(now, TimeZone::now).0;
+    let date = log.date.map_or(Ok(today), parse_date)?;
 
-    match log.period {
-        Some(str_period) => {
-            let ((from_hrs, from_min), (to_hrs, to_min)) = parse_period(&str_period);
-            let period = Period::new(Time::new(from_hrs, from_min), Time::new(to_hrs, to_min));
-            let breaks: Vec<Duration> = log
-                .breaks
+    match (log.period.is_empty(), log.time) {
+        (false, _) => {
+            if log.period.len() > 1 && !log.breaks.is_empty() {
+                return Err(Error::InvalidInput(
+                    "Breaks can not be given with several periods, the gaps between them are the breaks"
+                        .to_owned(),
+                ));
+            }
+            let periods = log
+                .period
                 .iter()
-                .map(|break_str| parse_time(break_str))
-                .map(|(hrs, min)| Duration::from_hm(hrs as i32, min as i32))
-                .collect();
-            CliResult::PeriodLog {
-                project: log.project,
-                period,
+                .map(|period_str| parse_period(period_str.trim()))
+                .collect::<Result<_>>()?;
+            Ok(CliResult::PeriodLog {
+                project,
+                periods,
                 date,
-                desc: log.description,
-                breaks,
-            }
+                desc: description,
+                breaks: parse_breaks(&log.breaks)?,
+                timezone,
+                force: log.force,
+            })
         }
-        None => {
-            let (hours, minutes) =
-                parse_time(&log.time.expect("Must supply either time or period!"));
-            let duration = Duration::from_hm(hours as i32, minutes as i32);
-            CliResult::SimpleLog {
-                project: log.project,
+        (true, Some(str_time)) => {
+            let duration = parse_duration(&str_time)?;
+            Ok(CliResult::SimpleLog {
+                project,
                 duration,
                 date,
-                desc: log.description,
-            }
+                desc: description,
+                force: log.force,
+            })
         }
+        (true, None) => Err(Error::InvalidInput(
+            "Must supply either a time or a period".to_owned(),
+        )),
     }
 }
 
-fn parse_init(project: String, opt_date: Option<String>) -> CliResult {
-    CliResult::Init {
-        project,
-        start_date: opt_date.map_or(now().0, parse_date),
+fn parse_absence(absence: CliAbsence) -> Result<CliResult> {
+    let from = absence.date.map_or(Ok(now().0), parse_date)?;
+    let to = absence.to.map_or(Ok(from.clone()), parse_date)?;
+    if from > to {
+        return Err(Error::InvalidInput(
+            "The absence can not end before it starts".to_owned(),
+        ));
     }
+
+    Ok(CliResult::Absence {
+        project: absence.name,
+        kind: absence.kind,
+        from,
+        to,
+        half_day: absence.half,
+        desc: absence.description,
+    })
 }
 
-fn parse_delete(project: String, date: Option<String>) -> CliResult {
-    CliResult::Delete {
+fn parse_init(
+    project: String,
+    opt_date: Option<String>,
+    opt_schedule: Option<String>,
+) -> Result<CliResult> {
+    Ok(CliResult::Init {
         project,
-        date: date.map_or(now().0, parse_date),
+        start_date: opt_date.map_or(Ok(now().0), parse_date)?,
+        schedule: opt_schedule.as_deref().map(parse_schedule).transpose()?,
+    })
+}
+
+fn parse_holidays(holidays: CliHolidays) -> Result<CliResult> {
+    let country = match (holidays.country, holidays.no_country) {
+        (_, true) => Some(None),
+        (Some(country), false) => Some(Some(country)),
+        (None, false) => None,
+    };
+
+    Ok(CliResult::Holidays {
+        project: holidays.name,
+        country,
+        import: holidays
+            .import
+            .as_deref()
+            .map(read_holiday_file)
+            .transpose()?,
+        clear: holidays.clear,
+        year: holidays.year.unwrap_or(now().0.into_ymd().0),
+    })
+}
+
+fn parse_list(list: CliList) -> Result<CliResult> {
+    let (from, to) = match list.date.map(parse_date).transpose()? {
+        Some(date) => (Some(date.clone()), Some(date)),
+        None => (
+            list.from.map(parse_date).transpose()?,
+            list.to.map(parse_date).transpose()?,
+        ),
+    };
+
+    Ok(CliResult::List {
+        project: list.name,
+        from,
+        to,
+    })
+}
+
+fn parse_import(import: CliImport, config: &Config) -> Result<CliResult> {
+    let columns = import
+        .column
+        .iter()
+        .map(|column| {
+            let invalid = || {
+                Error::InvalidInput(format!(
+                    "Invalid column '{}', expected field=header such as date=Day",
+                    column
+                ))
+            };
+            let (field, header) = column.split_once('=').ok_or_else(invalid)?;
+            let field = Field::from_str(field.trim(), true).map_err(|_| invalid())?;
+            Ok((field, header.trim().to_owned()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let format = import
+        .format
+        .unwrap_or_else(|| ImportFormat::of_file(&import.file));
+    let default_description = config.default_description.as_deref().unwrap_or("Work");
+
+    Ok(CliResult::Import {
+        project: import.name,
+        logs: read_import_file(&import.file, format, &columns, default_description)?,
+        force: import.force,
+    })
+}
+
+fn parse_edit(edit: CliEdit) -> Result<CliResult> {
+    let breaks = if edit.no_breaks {
+        Some(vec![])
+    } else if edit.breaks.is_empty() {
+        None
+    } else {
+        Some(parse_breaks(&edit.breaks)?)
+    };
+
+    Ok(CliResult::Edit {
+        project: edit.name,
+        id: edit.id,
+        edit: LogEdit {
+            duration: edit.time.as_deref().map(parse_duration).transpose()?,
+            period: edit.period.as_deref().map(parse_period).transpose()?,
+            date: edit.date.map(parse_date).transpose()?,
+            breaks,
+            description: edit.description,
+        },
+        force: edit.force,
+    })
+}
+
+fn parse_report(report: CliReport, config: &Config) -> Result<CliResult> {
+    let grouping = match (report.week, report.month) {
+        (true, _) => Grouping::Week,
+        (_, true) => Grouping::Month,
+        _ => config.output.report.unwrap_or(Grouping::Day),
+    };
+
+    Ok(CliResult::Report {
+        project: report.name,
+        grouping,
+        from: report.from.map(parse_date).transpose()?,
+        to: report.to.map(parse_date).transpose()?,
+    })
+}
+
+fn parse_delete(project: String, date: Option<String>) -> Result<CliResult> {
+    Ok(CliResult::Delete {
+        project,
+        date: date.map_or(Ok(now().0), parse_date)?,
+    })
+}
+
+fn parse_breaks(break_strs: &[String]) -> Result<Vec<Break>> {
+    break_strs
+        .iter()
+        .map(|break_str| parse_duration(break_str).map(Break::Duration))
+        .collect()
+}
+
+/// Parses up to seven comma separated times, one for each weekday starting on monday.
+/// Days left out are expected to be free.
+fn parse_schedule(schedule_str: &str) -> Result<Schedule> {
+    let days: Vec<&str> = schedule_str.split(',').collect();
+    if days.len() > 7 {
+        return Err(Error::InvalidInput(format!(
+            "A schedule can have at most seven days, got {}",
+            days.len()
+        )));
+    }
+
+    let mut hours: [Duration; 7] = std::array::from_fn(|_| Duration::from_m(0));
+    for (day, time_str) in days.iter().enumerate() {
+        if !time_str.trim().is_empty() {
+            hours[day] = parse_duration(time_str.trim())?;
+        }
+    }
+
+    Ok(Schedule::new(hours))
+}
+
+/// Parses an absolute date, where year and month default to the current ones, or a date
+/// relative to today such as `yesterday`, `-2`, `fri`, `last mon` or the ISO week date
+/// `2023-W14-3`.
+fn parse_date(date_str: String) -> Result<Date> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(?:(\d{4})-)?(?:([01]?\d)-)?([0123]?\d)$").unwrap();
+        static ref RELATIVE_RE: Regex = Regex::new(r"^([+-])(\d+)$").unwrap();
+        static ref WEEKDAY_RE: Regex = Regex::new(r"^(?:(last|next)\s+)?([a-z]+)$").unwrap();
+        static ref ISO_WEEK_RE: Regex = Regex::new(r"^(\d{4})-w(\d\d?)(?:-([1-7]))?$").unwrap();
+    }
+
+    let invalid = || Error::InvalidDate(date_str.clone());
+    let today = now().0;
+    let lower = date_str.trim().to_lowercase();
+
+    match lower.as_str() {
+        "today" => return Ok(today),
+        "yesterday" => return today.checked_add_days(-1).ok_or_else(invalid),
+        "tomorrow" => return today.checked_add_days(1).ok_or_else(invalid),
+        _ => {}
+    }
+
+    if let Some(caps) = RELATIVE_RE.captures(&lower) {
+        let days: i64 = parse_cap(caps.get(2), 0).ok_or_else(invalid)?;
+        let sign = if &caps[1] == "-" { -1 } else { 1 };
+        return today.checked_add_days(sign * days).ok_or_else(invalid);
+    }
+
+    if let Some(caps) = WEEKDAY_RE.captures(&lower) {
+        let weekday = parse_weekday(&caps[2]).ok_or_else(invalid)?;
+        let days_since = (today.weekday() + 7 - weekday) % 7;
+        let days = match caps.get(1).map(|word| word.as_str()) {
+            None => -(days_since as i64),
+            // The latest one before today
+            Some("last") if days_since == 0 => -7,
+            Some("last") => -(days_since as i64),
+            _ => 7 - days_since as i64,
+        };
+        return today.checked_add_days(days).ok_or_else(invalid);
+    }
+
+    if let Some(caps) = ISO_WEEK_RE.captures(&lower) {
+        let year = parse_cap(caps.get(1), 0).ok_or_else(invalid)?;
+        let week = parse_cap(caps.get(2), 0).ok_or_else(invalid)?;
+        let weekday: usize = parse_cap(caps.get(3), 1).ok_or_else(invalid)?;
+        return Date::from_iso_week(year, week, weekday - 1).ok_or_else(invalid);
+    }
+
+    let caps = RE.captures(&lower).ok_or_else(invalid)?;
+    let (y, m, d) = today.into_ymd();
+
+    let year = parse_cap(caps.get(1), y).ok_or_else(invalid)?;
+    let month = parse_cap(caps.get(2), m).ok_or_else(invalid)?;
+    let day = parse_cap(caps.get(3), d).ok_or_else(invalid)?;
+
+    let date = Date::new(year, month, day);
+    if date.is_valid() {
+        Ok(date)
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Weekday of a (possibly shortened) english name, where monday is 0
+fn parse_weekday(name: &str) -> Option<usize> {
+    if name.len() < 2 {
+        return None;
     }
+    WEEKDAYS
+        .iter()
+        .position(|weekday| weekday.to_lowercase().starts_with(name))
 }
 
-fn parse_date(date_str: String) -> Date {
+/// Parses a date written as YYYY-MM-DD, which is how files give dates
+pub(crate) fn parse_iso_date(date_str: &str) -> Result<Date> {
     lazy_static! {
-        static ref RE: Regex = Regex::new(r"(?:(\d{4})-)?(?:([01]?\d)-)?([0123]?\d)").unwrap();
+        static ref RE: Regex = Regex::new(r"^(\d{4})-(\d\d?)-(\d\d?)$").unwrap();
     }
 
-    let caps = RE
-        .captures(&date_str)
-        .expect("Submitted date does not match date regex!");
+    let invalid = || Error::InvalidDate(date_str.to_owned());
+    let caps = RE.captures(date_str).ok_or_else(invalid)?;
+    let year = parse_cap(caps.get(1), 0).ok_or_else(invalid)?;
+    let month = parse_cap(caps.get(2), 0).ok_or_else(invalid)?;
+    let day = parse_cap(caps.get(3), 0).ok_or_else(invalid)?;
 
-    let (y, m, d) = now().0.into_ymd();
+    let date = Date::new(year, month, day);
+    if date.is_valid() {
+        Ok(date)
+    } else {
+        Err(invalid())
+    }
+}
 
-    let year = parse_cap(caps.get(1), y);
-    let month = parse_cap(caps.get(2), m);
-    let day = parse_cap(caps.get(3), d); // TODO, better error handling?
+/// Parses `from-to[+days]`, where a period ending before it starts without any days
+/// given is taken to continue past midnight as long as it is not too long.
+pub(crate) fn parse_period(period_str: &str) -> Result<Period> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^([\d:]+)-([\d:]+)(?:\+(\d{1,3}))?$").unwrap();
+    }
 
-    // TODO: better check for valid date
-    assert!(day <= 31);
+    let invalid = || Error::InvalidPeriod(period_str.to_owned());
+    let caps = RE.captures(period_str).ok_or_else(invalid)?;
 
-    Date::new(year, month, day)
+    let from = parse_clock_time(&caps[1]).map_err(|_| invalid())?;
+    let to = parse_clock_time(&caps[2]).map_err(|_| invalid())?;
+    let days = caps
+        .get(3)
+        .map(|days| days.as_str().parse().map_err(|_| invalid()))
+        .transpose()?;
+    period_between(from, to, days)
 }
 
-// TODO: return option
-fn parse_period(period_str: &str) -> ((usize, usize), (usize, usize)) {
-    let mut split = period_str.split('-');
-    let from = split
-        .next()
-        .expect("Inavlid period which dould not be split!");
-    let to = split.next().expect("Input a valid period!");
+/// The period from one time to another ending `days` midnights later, or if not given
+/// past midnight when ending before it starts as long as it is not too long.
+pub(crate) fn period_between(from: Time, to: Time, days: Option<usize>) -> Result<Period> {
+    let written = format!("{}-{}", from, to);
+    if days.is_some_and(|days| days > MAX_PERIOD_DAYS) {
+        return Err(Error::InvalidInput(format!(
+            "Period '{}' ends more than {} days later",
+            written, MAX_PERIOD_DAYS
+        )));
+    }
+    let wraps = to < from;
+    let period = match days {
+        Some(days) => Period::spanning(from, to, days),
+        None => Period::new(from, to),
+    };
+
+    let reversed = match days {
+        Some(_) => period.duration() < Duration::from_m(0),
+        None => wraps && period.duration() > Duration::from_hm(MAX_OVERNIGHT_HOURS, 0),
+    };
+    if reversed {
+        return Err(Error::InvalidInput(format!(
+            "Period '{}' ends before it starts, add +1 to the end if it continues past midnight",
+            written
+        )));
+    }
+
+    Ok(period)
+}
+
+/// A time of day, where 24:00 is the end of the day
+pub(crate) fn parse_clock_time(time_str: &str) -> Result<Time> {
+    let (hours, minutes) = parse_time(time_str)?;
+    clock_time(hours, minutes).ok_or_else(|| Error::InvalidTime(time_str.to_owned()))
+}
+
+/// A time of day from its hours and minutes, if it is within the day
+pub(crate) fn clock_time(hours: usize, minutes: usize) -> Option<Time> {
+    (hours * 60 + minutes <= 24 * 60).then(|| Time::new(hours, minutes))
+}
 
-    (parse_time(from), parse_time(to))
+pub(crate) fn parse_duration(duration_str: &str) -> Result<Duration> {
+    let (hours, minutes) = parse_time(duration_str)?;
+    Ok(Duration::from_hm(hours as i32, minutes as i32))
 }
 
-// TODO: Return Option for better error report
-fn parse_time(time_str: &str) -> (usize, usize) {
+/// Parses `hours[:minutes]` with at most 4 digits of hours, where only minutes are
+/// checked to be in range
+pub(crate) fn parse_time(time_str: &str) -> Result<(usize, usize)> {
     lazy_static! {
-        static ref RE: Regex = Regex::new(r"(\d\d?)(?::(\d\d?))?").unwrap();
+        static ref RE: Regex = Regex::new(r"^(\d{1,4})(?::(\d\d?))?$").unwrap();
     }
 
-    let caps = RE.captures(time_str).expect("Invalid time parsing regex!");
+    let invalid = || Error::InvalidTime(time_str.to_owned());
+    let caps = RE.captures(time_str).ok_or_else(invalid)?;
 
-    let hours = parse_cap(caps.get(1), 0);
-    let minutes = parse_cap(caps.get(2), 0);
+    let hours = parse_cap(caps.get(1), 0).ok_or_else(invalid)?;
+    let minutes = parse_cap(caps.get(2), 0).ok_or_else(invalid)?;
+    if minutes >= 60 {
+        return Err(invalid());
+    }
 
-    (hours, minutes)
+    Ok((hours, minutes))
 }
 
-fn parse_cap<T: FromStr>(cap: Option<regex::Match>, default: T) -> T
-where
-    <T as FromStr>::Err: Debug,
-{
+/// Parses a capture, or gives the default if it did not match. None if it could not be parsed.
+fn parse_cap<T: FromStr>(cap: Option<regex::Match>, default: T) -> Option<T> {
     match cap {
-        None => default,
-        Some(string) => string
-            .as_str()
-            .parse::<T>()
-            .expect("Was unable to parse a regex capture!"), // TODO: Too broad! Return Result
+        None => Some(default),
+        Some(string) => string.as_str().parse::<T>().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(date_str: &str) -> Date {
+        parse_date(date_str.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn parse_date_relative_to_today() {
+        let today = now().0;
+        assert_eq!(date("today"), today);
+        assert_eq!(date("Yesterday"), today.add_days(-1));
+        assert_eq!(date("tomorrow"), today.add_days(1));
+        assert_eq!(date("-2"), today.add_days(-2));
+        assert_eq!(date("+10"), today.add_days(10));
+    }
+
+    #[test]
+    fn parse_date_weekdays() {
+        let today = now().0;
+        for name in ["mon", "tuesday", "We", "thu", "fri", "sat", "sunday"] {
+            let latest = date(name);
+            let days_ago = today.days_since(&latest);
+            assert!(
+                (0..7).contains(&days_ago),
+                "{} was {} days ago",
+                name,
+                days_ago
+            );
+            let last = if latest == today {
+                latest.add_days(-7)
+            } else {
+                latest.clone()
+            };
+            assert_eq!(date(&format!("last {}", name)), last);
+            assert_eq!(date(&format!("next {}", name)), latest.add_days(7));
+        }
+        assert_eq!(date("fri").weekday(), 4);
+        assert!(parse_date("m".to_owned()).is_err());
+        assert!(parse_date("someday".to_owned()).is_err());
+    }
+
+    #[test]
+    fn parse_date_iso_weeks() {
+        assert_eq!(date("2023-W14-3"), Date::new(2023, 4, 5));
+        assert_eq!(date("2023-w14"), Date::new(2023, 4, 3));
+        assert_eq!(date("2020-W53-7"), Date::new(2021, 1, 3));
+        assert_eq!(date("2025-W01-1"), Date::new(2024, 12, 30));
+        assert!(parse_date("2021-W53".to_owned()).is_err());
+        assert!(parse_date("2023-W14-8".to_owned()).is_err());
+    }
+
+    #[test]
+    fn parse_date_checks_the_calendar() {
+        assert_eq!(date("2024-02-29"), Date::new(2024, 2, 29));
+        assert!(parse_date("2023-02-29".to_owned()).is_err());
+        assert!(parse_date("2023-04-31".to_owned()).is_err());
+        assert!(parse_date("0000-01-01".to_owned()).is_err());
+        assert!(parse_date("0000-W01".to_owned()).is_err());
+    }
+
+    #[test]
+    fn parse_times_out_of_range() {
+        assert!(matches!(
+            parse_time("99999999999"),
+            Err(Error::InvalidTime(_))
+        ));
+        assert!(matches!(
+            parse_duration("99999:00"),
+            Err(Error::InvalidTime(_))
+        ));
+        assert_eq!(
+            parse_duration("9999:59").unwrap(),
+            Duration::from_hm(9999, 59)
+        );
+        assert!(parse_clock_time("25").is_err());
+        assert!(parse_period("999999999999999999-10").is_err());
+        assert!(parse_period("8-10+9999999999999999999").is_err());
+        assert!(parse_period("8-10+367").is_err());
+        assert_eq!(
+            parse_period("8-10+366").unwrap().duration(),
+            Duration::from_hm(366 * 24 + 2, 0)
+        );
+    }
+
+    #[test]
+    fn parse_date_out_of_range() {
+        for date_str in [
+            "+99999999999",
+            "-9999999",
+            "+99999999999999999999",
+            "-3000000",
+        ] {
+            assert!(matches!(
+                parse_date(date_str.to_owned()),
+                Err(Error::InvalidDate(_))
+            ));
+        }
     }
 }
@@ -1,123 +1,895 @@
+mod export;
+mod files;
+mod import;
+mod journal;
+mod migrations;
+mod report;
+mod sanity;
+mod schedule;
+mod session;
+mod storage;
+mod vacation;
 mod worklog;
 
-pub use self::worklog::{DurationLog, PeriodLog, WorkLog};
+pub use self::export::ExportFormat;
+pub use self::import::{read_import_file, Field, ImportFormat};
+pub use self::report::Grouping;
+pub use self::schedule::Schedule;
+pub use self::storage::StorageKind;
+pub use self::vacation::Accrual;
+pub use self::worklog::{Absence, Break, LogEdit, WorkLog};
 
+use self::files::ProjectLock;
+use self::report::ReportRow;
+use self::session::Session;
+use self::storage::Storage;
+use self::vacation::VacationPolicy;
+
+use crate::error::{Error, Result};
+use crate::holidays::{Country, Holiday, HolidayCalendar};
 use crate::time::{now, Date, Duration};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::PathBuf;
 
-const FILE_TYPE: &str = "json";
-
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ProjectLog {
+    /// Version of the file format, upgraded by the migrations when read
+    version: usize,
     name: String,
     logs: Vec<WorkLog>,
+    #[serde(default)]
+    next_id: usize,
     start_date: Date,
+    #[serde(default)]
+    schedule: Schedule,
+    #[serde(default = "sanity::default_max_per_day")]
+    max_per_day: Duration,
+    #[serde(default)]
+    holidays: HolidayCalendar,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vacation: Option<VacationPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session: Option<Session>,
+    /// Last date any hours are expected, such as when a contract ends
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end_date: Option<Date>,
+    /// Hidden from the list of projects and closed for new logs
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    archived: bool,
+    /// Held from opening the project until the command is done with it
+    #[serde(skip)]
+    lock: Option<ProjectLock>,
     // TODO: Add different projects
-    // TODO: Be able to end projects
-    // TODO: Rename projects
-    // TODO: Delete a log (Probably not edit, as it would be a bit of a hassle)
 }
 
 impl ProjectLog {
-    pub fn init(name: String, start_date: Date) {
+    pub fn init(name: String, start_date: Date, schedule: Option<Schedule>) -> Result<()> {
         let project = Self {
+            version: migrations::CURRENT_VERSION,
             name,
             logs: vec![],
+            next_id: 1,
             start_date,
+            schedule: schedule.unwrap_or_default(),
+            max_per_day: sanity::default_max_per_day(),
+            holidays: HolidayCalendar::default(),
+            vacation: None,
+            session: None,
+            end_date: None,
+            archived: false,
+            lock: None,
         };
 
-        assert!(
-            !&Self::get_path(&project.name).as_path().exists(),
-            "Project already exists!"
+        if Self::exists(&project.name)? {
+            return Err(Error::ProjectExists(project.name));
+        }
+
+        project.save()
+    }
+
+    pub fn exists(project_name: &str) -> Result<bool> {
+        storage::open()?.exists(project_name)
+    }
+
+    /// Adds the logs unless they look wrong, such as overlapping other logs,
+    /// where forcing adds them anyway with a warning.
+    pub fn log(project_name: &str, worklogs: Vec<WorkLog>, force: bool) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        project.check_new_logs(&worklogs, force)?;
+        project.add_logs(worklogs)
+    }
+
+    /// Adds logs from another time tracker, skipping those already logged
+    pub fn import(project_name: &str, worklogs: Vec<WorkLog>, force: bool) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+
+        let total = worklogs.len();
+        let mut new_logs: Vec<WorkLog> = vec![];
+        for worklog in worklogs {
+            let logged = project.logs.iter().chain(&new_logs);
+            if !logged.clone().any(|log| log.same_work(&worklog)) {
+                new_logs.push(worklog);
+            }
+        }
+        let duplicates = total - new_logs.len();
+        if new_logs.is_empty() {
+            println!(
+                "Nothing imported, all {} logs are already in project {}",
+                total, project_name
+            );
+            return Ok(());
+        }
+
+        project.check_new_logs(&new_logs, force)?;
+        let imported = new_logs.len();
+        project.insert_logs(new_logs)?;
+        project.save()?;
+        println!(
+            "Imported {} logs to project {}, skipping {} already logged",
+            imported, project_name, duplicates
         );
+        println!("\nRemaining flex time: {}", project.get_flex_time());
+        Ok(())
+    }
+
+    /// Logs an absence for every date in the range which is expected to be worked
+    pub fn absence(
+        project_name: &str,
+        kind: Absence,
+        from: Date,
+        to: Date,
+        half_day: bool,
+        desc: Option<String>,
+    ) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        let absences: Vec<WorkLog> = if from == to {
+            vec![WorkLog::new_absence(kind, from, half_day, desc)]
+        } else {
+            let absences: Vec<WorkLog> = to
+                .dates_since(&from)
+                .filter(|date| project.get_expected_time(date, date) > Duration::from_m(0))
+                .map(|date| WorkLog::new_absence(kind, date, half_day, desc.clone()))
+                .collect();
+            if absences.is_empty() {
+                return Err(Error::InvalidInput(format!(
+                    "No workdays between {} and {}",
+                    from, to
+                )));
+            }
+            absences
+        };
 
-        project.save();
+        project.add_logs(absences)
     }
 
-    pub fn log(project_name: &str, worklog: WorkLog) {
-        let mut project = Self::open(project_name);
-        let log_msg = worklog.to_string();
-        project.logs.push(worklog);
-        project.save();
+    pub fn schedule(
+        project_name: &str,
+        schedule: Option<Schedule>,
+        max_per_day: Option<Duration>,
+    ) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        if schedule.is_none() && max_per_day.is_none() {
+            println!(
+                "Schedule for project {}:\n{}\nMax per day: {}",
+                project_name, project.schedule, project.max_per_day
+            );
+            return Ok(());
+        }
+
+        if let Some(schedule) = schedule {
+            project.schedule = schedule;
+        }
+        if let Some(max_per_day) = max_per_day {
+            project.max_per_day = max_per_day;
+        }
+        project.save()?;
         println!(
-            "Work logged:\n{}\n\nRemaining flex time: {}",
-            log_msg,
+            "Updated schedule for project {}:\n{}\nMax per day: {}\n\nRemaining flex time: {}",
+            project_name,
+            project.schedule,
+            project.max_per_day,
             project.get_flex_time()
         );
+        Ok(())
     }
 
-    pub fn delete(project_name: &str, date: Date) {
-        let mut project = Self::open(project_name);
-        project.logs = project.logs
-            .into_iter()
-            .filter(|log| log.get_date() != date)
+    pub fn holidays(
+        project_name: &str,
+        country: Option<Option<Country>>,
+        import: Option<Vec<Holiday>>,
+        clear: bool,
+        year: usize,
+    ) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        let changed = country.is_some() || import.is_some() || clear;
+
+        if clear {
+            project.holidays.clear_imported();
+        }
+        if let Some(country) = country {
+            project.holidays.set_country(country);
+        }
+        if let Some(holidays) = import {
+            let added = project.holidays.import(holidays);
+            println!("Imported {} new holidays", added);
+        }
+        if changed {
+            project.save()?;
+        }
+
+        println!(
+            "Holidays for project {} ({}):",
+            project_name, project.holidays
+        );
+        for holiday in project
+            .holidays
+            .between(&Date::new(year, 1, 1), &Date::new(year, 12, 31))
+        {
+            println!("{}", holiday);
+        }
+        if changed {
+            println!("\nRemaining flex time: {}", project.get_flex_time());
+        }
+        Ok(())
+    }
+
+    pub fn vacation(
+        project_name: &str,
+        days_per_year: Option<f32>,
+        accrual: Option<Accrual>,
+        max_carry_over: Option<f32>,
+    ) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+
+        if days_per_year.is_some() || accrual.is_some() || max_carry_over.is_some() {
+            match &mut project.vacation {
+                Some(policy) => policy.update(days_per_year, accrual, max_carry_over),
+                None => {
+                    let days_per_year = days_per_year.ok_or(Error::InvalidInput(
+                        "Set the number of vacation days per year with --days first".to_owned(),
+                    ))?;
+                    project.vacation = Some(VacationPolicy::new(
+                        days_per_year,
+                        accrual.unwrap_or(Accrual::Yearly),
+                        max_carry_over.unwrap_or(0.0),
+                    ))
+                }
+            }
+            project.save()?;
+        }
+
+        let policy = project.vacation.as_ref().ok_or(Error::InvalidState(
+            "No vacation days set for this project, use --days to set them".to_owned(),
+        ))?;
+        let vacation: Vec<(Date, f32)> = project
+            .logs
+            .iter()
+            .map(|log| (log.get_date(), log.get_vacation_days()))
+            .filter(|(_, days)| *days > 0.0)
             .collect();
-        project.save();
+        let balance = policy.balance(&project.start_date, &now().0, &vacation);
+
+        println!(
+            "Vacation for project {} ({}):\n{}",
+            project_name, policy, balance
+        );
+        Ok(())
+    }
+
+    /// Prints worked and expected hours for each period in the range, along with
+    /// the flex time at the end of it.
+    pub fn report(
+        project_name: &str,
+        grouping: Grouping,
+        from: Option<Date>,
+        to: Option<Date>,
+    ) -> Result<()> {
+        let project = Self::read(project_name)?;
+        let from = from.unwrap_or(project.start_date.clone());
+        let to = to.unwrap_or_else(|| match &project.end_date {
+            Some(end_date) => now().0.min(end_date.clone()),
+            None => now().0,
+        });
+        if from > to {
+            return Err(Error::InvalidInput(
+                "The report can not end before it starts".to_owned(),
+            ));
+        }
 
-        println!("Removed all logs for project {} for the date {}", project_name, date);
+        let mut flex = project.get_flex_time_until(&from.add_days(-1));
+        let mut total_worked = Duration::from_m(0);
+        let mut total_expected = Duration::from_m(0);
+
+        println!(
+            "{:<16} | {:<24} | {:<24} | Flex",
+            "Period", "Worked", "Expected"
+        );
+        for (start, end) in grouping.periods(&from, &to) {
+            let worked = project.get_worked_time(&start, &end);
+            let counted_from = (&start).max(&project.start_date);
+            let expected = project.get_expected_time(counted_from, &end);
+            if grouping == Grouping::Day && worked == expected && worked == Duration::from_m(0) {
+                continue;
+            }
+
+            flex = flex + worked.clone() - expected.clone();
+            total_worked = total_worked + worked.clone();
+            total_expected = total_expected + expected.clone();
+            println!(
+                "{}",
+                ReportRow::new(grouping.label(&start), worked, expected, flex.clone())
+            );
+        }
+        println!(
+            "{}",
+            ReportRow::new("Total".to_owned(), total_worked, total_expected, flex)
+        );
+        Ok(())
+    }
+
+    /// Prints all logs within the dates, both inclusive, ordered by date
+    pub fn list(project_name: &str, from: Option<Date>, to: Option<Date>) -> Result<()> {
+        let project = Self::read(project_name)?;
+        let logs = project.logs_between(from.as_ref(), to.as_ref());
+
+        if logs.is_empty() {
+            println!("No logs found for project {}", project_name);
+        }
+        for log in logs {
+            println!("{:>4}  {}", format!("#{}", log.get_id()), log);
+        }
+        Ok(())
     }
 
-    pub fn wipe(project_name: &str) {
-        // Just nuke the whole directory
-        let path = Self::get_path(project_name);
-        assert!(path.as_path().exists(), "Project does not exist!");
-        std::fs::remove_file(path)
-            .expect("Unable to remove logs.");
+    pub fn edit(project_name: &str, id: usize, edit: LogEdit, force: bool) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        let index = project.find_log(id)?;
+        let edited = project.logs.remove(index).edit(edit)?;
+        project.check_new_logs(std::slice::from_ref(&edited), force)?;
+        let log_msg = edited.to_string();
+        project.logs.insert(index, edited);
+        project.save()?;
+
+        println!(
+            "Edited log #{}:\n{}\n\nRemaining flex time: {}",
+            id,
+            log_msg,
+            project.get_flex_time()
+        );
+        Ok(())
+    }
+
+    pub fn remove(project_name: &str, id: usize) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        let index = project.find_log(id)?;
+        let removed = project.logs.remove(index);
+        project.save()?;
+
+        println!(
+            "Removed log #{}:\n{}\n\nRemaining flex time: {}",
+            id,
+            removed,
+            project.get_flex_time()
+        );
+        Ok(())
+    }
+
+    pub fn start(project_name: &str) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        if let Some(session) = &project.session {
+            return Err(Error::InvalidState(format!(
+                "A session is already running since {}",
+                session
+            )));
+        }
+
+        let session = Session::start();
+        println!("Started working on {} {}", project_name, session);
+        project.session = Some(session);
+        project.save()
+    }
+
+    pub fn pause(project_name: &str) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        let start = project.running_session()?.pause()?;
+        println!("Paused work on {} at {}", project_name, start);
+        project.save()
+    }
+
+    pub fn resume(project_name: &str) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        let pause = project.running_session()?.resume()?;
+        println!(
+            "Resumed work on {} after a break of {}",
+            project_name,
+            pause.duration()
+        );
+        project.save()
+    }
+
+    pub fn stop(project_name: &str, desc: String, breaks: Vec<Break>, force: bool) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        let session = project.session.take().ok_or_else(Self::no_session)?;
+        let worklogs = vec![session.stop(desc, breaks)];
+
+        project.check_new_logs(&worklogs, force)?;
+        project.add_logs(worklogs)
+    }
+
+    pub fn delete(project_name: &str, date: Date) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        project.logs.retain(|log| log.get_date() != date);
+        project.save()?;
+
+        println!(
+            "Removed all logs for project {} for the date {}",
+            project_name, date
+        );
+        Ok(())
+    }
+
+    pub fn wipe(project_name: &str) -> Result<()> {
+        let storage = storage::open()?;
+        if !storage.exists(project_name)? {
+            return Err(Error::ProjectNotFound(project_name.to_owned()));
+        }
+        let _lock = ProjectLock::acquire(project_name)?;
+        journal::record(
+            &*storage,
+            project_name,
+            Self::load(&*storage, project_name)?,
+            None,
+        )?;
+        storage.remove(project_name)?;
 
         println!("Removed all logs for project {}", project_name);
+        Ok(())
+    }
+
+    /// Lists the projects with their flex time and latest log, including archived ones if `all`
+    pub fn projects(all: bool) -> Result<()> {
+        let names = storage::open()?.names()?;
+        if names.is_empty() {
+            println!("There are no projects yet, create one with `init`");
+            return Ok(());
+        }
+
+        println!(
+            "{:<16} | {:<10} | {:<24} | Last log",
+            "Project", "Start", "Flex"
+        );
+        for name in names {
+            let project = match Self::read(&name) {
+                Ok(project) => project,
+                Err(err) => {
+                    println!("{:<16} | {}", name, err);
+                    continue;
+                }
+            };
+            if project.archived && !all {
+                continue;
+            }
+
+            let last_log = project.logs.iter().map(WorkLog::get_date).max();
+            let mut row = format!(
+                "{:<16} | {} | {:<24} | {}",
+                name,
+                project.start_date,
+                project.get_flex_time().to_string(),
+                last_log.map_or("None".to_owned(), |date| date.to_string())
+            );
+            if let Some(end_date) = &project.end_date {
+                row.push_str(&format!(" (ends {})", end_date));
+            }
+            if project.archived {
+                row.push_str(" (archived)");
+            }
+            println!("{}", row);
+        }
+        Ok(())
+    }
+
+    /// Gives a project a new name, moving its files along with it
+    pub fn rename(project_name: &str, new_name: &str) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        if Self::exists(new_name)? {
+            return Err(Error::ProjectExists(new_name.to_owned()));
+        }
+
+        let storage = storage::open()?;
+        storage.rename(project_name, new_name)?;
+        journal::record_rename(&*storage, new_name, project_name)?;
+        project.name = new_name.to_owned();
+        storage.store(new_name, &project.serialize())?;
+
+        // Released before removing it, as the project no longer goes by the old name
+        drop(project);
+        ProjectLock::remove(project_name)?;
+        println!("Renamed project {} to {}", project_name, new_name);
+        Ok(())
+    }
+
+    /// Hides a project and closes it for new logs, or opens it again if `restore`
+    pub fn archive(project_name: &str, restore: bool) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        if project.archived != restore {
+            let state = if restore { "is not" } else { "is already" };
+            return Err(Error::InvalidState(format!(
+                "Project {} {} archived",
+                project_name, state
+            )));
+        }
+
+        project.archived = !restore;
+        project.save()?;
+        if restore {
+            println!("Restored project {} from the archive", project_name);
+        } else {
+            println!("Archived project {}", project_name);
+        }
+        Ok(())
+    }
+
+    /// Stops expecting any hours after a date, or keeps expecting them forever if None
+    pub fn end(project_name: &str, end_date: Option<Date>) -> Result<()> {
+        let mut project = Self::open(project_name)?;
+        if end_date
+            .as_ref()
+            .is_some_and(|end_date| end_date < &project.start_date)
+        {
+            return Err(Error::InvalidInput(format!(
+                "Project {} can not end before it starts on {}",
+                project_name, project.start_date
+            )));
+        }
+
+        project.end_date = end_date;
+        project.save()?;
+        match &project.end_date {
+            Some(end_date) => println!("Project {} ends on {}", project_name, end_date),
+            None => println!("Project {} no longer has an end date", project_name),
+        }
+        println!("\nRemaining flex time: {}", project.get_flex_time());
+        Ok(())
+    }
+
+    /// Writes the logs between two dates to stdout in a format for other programs
+    pub fn export(
+        project_name: &str,
+        format: ExportFormat,
+        from: Option<Date>,
+        to: Option<Date>,
+    ) -> Result<()> {
+        let project = Self::read(project_name)?;
+        let logs = project.logs_between(from.as_ref(), to.as_ref());
+        format.write(&logs, std::io::stdout().lock())
+    }
+
+    /// Reverts the latest changes to a project
+    pub fn undo(project_name: &str, count: usize) -> Result<()> {
+        let storage = storage::open()?;
+        let _lock = ProjectLock::acquire(project_name)?;
+        let current = Self::load(&*storage, project_name)?;
+        let (undone, before) = journal::undo(&*storage, project_name, count, current)?;
+
+        match &before {
+            Some(project) => storage.store(project_name, &project.serialize())?,
+            None => storage.remove(project_name)?,
+        }
+
+        println!("Undid the following changes to project {}:", project_name);
+        for entry in undone {
+            println!("{}", entry);
+        }
+        if let Some(project) = before {
+            println!("\nRemaining flex time: {}", project.get_flex_time());
+        }
+        Ok(())
+    }
+
+    pub fn history(project_name: &str, count: usize) -> Result<()> {
+        let storage = storage::open()?;
+        let history = journal::history(&*storage, project_name, count)?;
+
+        if history.is_empty() {
+            println!("No changes recorded for project {}", project_name);
+        }
+        for (entry, undone) in history {
+            println!("{}{}", entry, if undone { " (undone)" } else { "" });
+        }
+        Ok(())
+    }
+
+    /// Moves all projects along with their journals to another kind of storage,
+    /// keeping the old data aside
+    pub fn migrate_storage(kind: StorageKind) -> Result<()> {
+        let current = storage::open()?;
+        if current.kind() == kind {
+            return Err(Error::InvalidState(format!(
+                "Projects are already saved as {}",
+                kind
+            )));
+        }
+
+        let names = current.names()?;
+        let target = storage::create(kind)?;
+        for name in &names {
+            if target.exists(name)? {
+                return Err(Error::ProjectExists(format!("{} ({})", name, kind)));
+            }
+        }
+
+        let mut locks = vec![];
+        let copied = names.iter().try_for_each(|name| {
+            locks.push(ProjectLock::acquire(name)?);
+            let content = current
+                .load(name)?
+                .ok_or_else(|| Error::ProjectNotFound(name.to_owned()))?;
+            let project = Self::parse(&*current, name, &content)?;
+            for line in current.journal(name)? {
+                target.append_journal(name, &line)?;
+            }
+            target.store(name, &project.serialize())
+        });
+        if let Err(err) = copied {
+            // Without this, a half filled database would be used instead of the files
+            let _ = target.retire();
+            return Err(err);
+        }
+
+        let retired = current.retire()?;
+        println!(
+            "Moved {} projects to {}, the old data is kept in {}",
+            names.len(),
+            kind,
+            retired.display()
+        );
+        Ok(())
+    }
+
+    fn running_session(&mut self) -> Result<&mut Session> {
+        self.session.as_mut().ok_or_else(Self::no_session)
     }
 
-    fn open(project_name: &str) -> Self {
-        let path = Self::get_path(project_name);
+    fn no_session() -> Error {
+        Error::InvalidState(
+            "No session is running for this project, start one with `start`".to_owned(),
+        )
+    }
 
-        match std::fs::read_to_string(path) {
-            Ok(file_str) => {
-                serde_json::from_str(&file_str).expect("ProjectLog can't be deserialized!")
+    /// Fails if the logs look wrong, such as overlapping other logs, unless forced
+    /// which only warns about it
+    fn check_new_logs(&self, worklogs: &[WorkLog], force: bool) -> Result<()> {
+        let others: Vec<ProjectLog> = storage::open()?
+            .names()?
+            .iter()
+            .filter(|name| name.as_str() != self.name)
+            .filter_map(|name| Self::read(name).ok())
+            .collect();
+        let problems = sanity::problems(self, worklogs, &others);
+        if !problems.is_empty() {
+            if !force {
+                return Err(Error::Conflict(problems));
+            }
+            for problem in problems {
+                eprintln!("Warning: {}", problem);
             }
-            Err(_) => panic!("Initialize the project before logging to it."),
         }
+        Ok(())
     }
 
-    // TODO: return result? For better error handling
-    fn save(&self) {
-        folders::create_folders();
+    fn add_logs(&mut self, worklogs: Vec<WorkLog>) -> Result<()> {
+        let log_msgs = self
+            .insert_logs(worklogs)?
+            .iter()
+            .map(|log| format!("#{} {}", log.get_id(), log))
+            .collect::<Vec<_>>();
+        self.save()?;
+        println!(
+            "Work logged:\n{}\n\nRemaining flex time: {}",
+            log_msgs.join("\n"),
+            self.get_flex_time()
+        );
+        Ok(())
+    }
+
+    /// Gives the logs ids and adds them to the project, returning the added logs
+    fn insert_logs(&mut self, worklogs: Vec<WorkLog>) -> Result<&[WorkLog]> {
+        if self.archived {
+            return Err(Error::InvalidState(format!(
+                "Project {} is archived, restore it with `archive --restore` first",
+                self.name
+            )));
+        }
 
-        let path = Self::get_path(&self.name);
-        let serialized_str = serde_json::to_string(self).expect("Failed to serialize project log.");
+        let first = self.logs.len();
+        for mut worklog in worklogs {
+            worklog.set_id(self.next_id);
+            self.next_id += 1;
+            self.logs.push(worklog);
+        }
+        Ok(&self.logs[first..])
+    }
 
-        std::fs::write(path, &serialized_str).expect("Could not save file.");
+    /// Logs from one date until another, in the order they were worked
+    fn logs_between(&self, from: Option<&Date>, to: Option<&Date>) -> Vec<&WorkLog> {
+        let mut logs: Vec<&WorkLog> = self
+            .logs
+            .iter()
+            .filter(|log| from.is_none_or(|from| &log.get_date() >= from))
+            .filter(|log| to.is_none_or(|to| &log.get_date() <= to))
+            .collect();
+        logs.sort_by_key(|log| (log.get_date(), log.get_id()));
+        logs
+    }
+
+    fn find_log(&self, id: usize) -> Result<usize> {
+        self.logs
+            .iter()
+            .position(|log| log.get_id() == id)
+            .ok_or(Error::LogNotFound(id))
+    }
+
+    /// Reads a project to change it, waiting for anyone else changing it to finish first
+    fn open(project_name: &str) -> Result<Self> {
+        if !Self::exists(project_name)? {
+            return Err(Error::ProjectNotFound(project_name.to_owned()));
+        }
+
+        let lock = ProjectLock::acquire(project_name)?;
+        let mut project = Self::read(project_name)?;
+        project.lock = Some(lock);
+        Ok(project)
+    }
+
+    /// Reads a project without changing it, which is safe as saving replaces the whole project
+    fn read(project_name: &str) -> Result<Self> {
+        Self::load(&*storage::open()?, project_name)?
+            .ok_or_else(|| Error::ProjectNotFound(project_name.to_owned()))
+    }
+
+    /// Reads a project from a storage, or None if there is no such project
+    fn load(storage: &dyn Storage, project_name: &str) -> Result<Option<Self>> {
+        storage
+            .load(project_name)?
+            .map(|content| Self::parse(storage, project_name, &content))
+            .transpose()
+    }
+
+    /// Reads a saved project, upgrading it from older versions
+    fn parse(storage: &dyn Storage, project_name: &str, content: &str) -> Result<Self> {
+        let corrupt = |reason: String| Error::Corrupt {
+            path: storage.location(project_name),
+            reason,
+        };
+        let mut value: serde_json::Value =
+            serde_json::from_str(content).map_err(|err| corrupt(err.to_string()))?;
+        migrations::migrate(&mut value).map_err(corrupt)?;
+        let mut project: Self =
+            serde_json::from_value(value).map_err(|err| corrupt(err.to_string()))?;
+        // The storage decides the name, even if an undone rename says otherwise
+        project.name = project_name.to_owned();
+        project.assign_missing_ids();
+        Ok(project)
+    }
+
+    /// Gives ids to logs saved before logs had them
+    fn assign_missing_ids(&mut self) {
+        let max_id = self.logs.iter().map(WorkLog::get_id).max().unwrap_or(0);
+        self.next_id = self.next_id.max(max_id + 1);
+        for log in self.logs.iter_mut().filter(|log| log.get_id() == 0) {
+            log.set_id(self.next_id);
+            self.next_id += 1;
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let storage = storage::open()?;
+        let before = Self::load(&*storage, &self.name)?;
+        journal::record(&*storage, &self.name, before, Some(self))?;
+        storage.store(&self.name, &self.serialize())
+    }
+
+    fn serialize(&self) -> String {
+        serde_json::to_string(self).expect("Failed to serialize project log.")
     }
 
     // NOTE: Could memoise to save time if logs become lengthy
     fn get_flex_time(&self) -> Duration {
         let worked_hours: Duration = self.logs.iter().map(|log| log.get_duration()).sum();
-        let expected_hours = now().0.weekdays_since(&self.start_date) * 8;
+        let expected_hours = self.get_expected_time(&self.start_date, &now().0);
+
+        worked_hours - expected_hours
+    }
+
+    /// Flex time at the end of a date, counting all logs until then
+    fn get_flex_time_until(&self, date: &Date) -> Duration {
+        let worked_hours: Duration = self
+            .worked_per_date()
+            .filter(|(log_date, _)| log_date <= date)
+            .map(|(_, duration)| duration)
+            .sum();
+        let expected_hours = self.get_expected_time(&self.start_date, date);
+
+        worked_hours - expected_hours
+    }
+
+    fn get_worked_time(&self, from: &Date, to: &Date) -> Duration {
+        self.worked_per_date()
+            .filter(|(date, _)| date >= from && date <= to)
+            .map(|(_, duration)| duration)
+            .sum()
+    }
 
-        worked_hours - Duration::from_hm(expected_hours as i32, 0)
+    /// Worked time on each date, split over the dates of periods past midnight
+    fn worked_per_date(&self) -> impl Iterator<Item = (Date, Duration)> + '_ {
+        self.logs.iter().flat_map(WorkLog::get_duration_per_date)
     }
 
-    fn get_path(project: &str) -> PathBuf {
-        let mut path = folders::log_folder();
-        path.set_file_name(project);
-        path.set_extension(FILE_TYPE);
-        path
+    /// Hours expected by the schedule from one date until another, except on holidays
+    /// and the parts of days excused by absences
+    fn get_expected_time(&self, from: &Date, to: &Date) -> Duration {
+        let holidays: BTreeSet<Date> = self
+            .holidays
+            .between(from, to)
+            .into_iter()
+            .map(|holiday| holiday.date().clone())
+            .collect();
+
+        let mut logs_by_date: BTreeMap<Date, Vec<&WorkLog>> = BTreeMap::new();
+        for log in &self.logs {
+            logs_by_date.entry(log.get_date()).or_default().push(log);
+        }
+
+        to.dates_since(from)
+            .filter(|date| {
+                self.end_date
+                    .as_ref()
+                    .is_none_or(|end_date| date <= end_date)
+            })
+            .filter(|date| !holidays.contains(date))
+            .map(|date| {
+                let expected = self.schedule.expected(&date);
+                let excused: Duration = logs_by_date
+                    .get(&date)
+                    .into_iter()
+                    .flatten()
+                    .map(|log| log.get_excused(&expected))
+                    .sum();
+                expected.clone() - excused.min(expected)
+            })
+            .sum()
     }
 }
 
+/// Keeps the projects in a folder instead of the data folder of the OS.
+/// Only the first call has any effect.
+pub fn set_data_dir(path: PathBuf) {
+    let _ = folders::DATA_DIR.set(path);
+}
+
 mod folders {
+    use crate::error::{Error, Result};
     use std::path::PathBuf;
+    use std::sync::OnceLock;
 
     const BIN_NAME: &str = env!("CARGO_PKG_NAME");
 
-    pub fn log_folder() -> PathBuf {
-        let mut path = dirs::data_dir().expect("Could not find shared data folder.");
+    pub static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+    // Projects used to be meant for a logs subfolder, but always ended up directly in this one
+    pub fn log_folder() -> Result<PathBuf> {
+        if let Some(path) = DATA_DIR.get() {
+            return Ok(path.clone());
+        }
+        let mut path = dirs::data_dir().ok_or(Error::NoDataFolder)?;
         path.push(BIN_NAME);
-        path.push("logs");
-        path
+        Ok(path)
     }
 
-    pub fn create_folders() {
-        std::fs::create_dir_all(log_folder()).expect("Failed to create the data folder.");
+    pub fn create_folders() -> Result<()> {
+        let path = log_folder()?;
+        std::fs::create_dir_all(&path).map_err(|err| Error::io(&path, err))
     }
 }
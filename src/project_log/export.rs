@@ -0,0 +1,175 @@
+use clap::ValueEnum;
+
+use super::WorkLog;
+use crate::error::{Error, Result};
+use crate::time::Duration;
+use std::io::Write;
+use std::path::Path;
+
+const CSV_HEADER: [&str; 8] = [
+    "date",
+    "start",
+    "end",
+    "break_minutes",
+    "minutes",
+    "hours",
+    "absence",
+    "description",
+];
+
+/// File formats logs can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// One row per log, with the duration in both minutes and decimal hours
+    Csv,
+}
+
+impl ExportFormat {
+    /// Writes the logs in the format, in the order given
+    pub fn write(&self, logs: &[&WorkLog], out: impl Write) -> Result<()> {
+        match self {
+            ExportFormat::Csv => write_csv(logs, out),
+        }
+    }
+}
+
+fn write_csv(logs: &[&WorkLog], out: impl Write) -> Result<()> {
+    let failed = |err: csv::Error| Error::io(Path::new("stdout"), err.into());
+
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(CSV_HEADER).map_err(failed)?;
+    for log in logs {
+        writer.write_record(csv_row(log)).map_err(failed)?;
+    }
+    writer
+        .flush()
+        .map_err(|err| Error::io(Path::new("stdout"), err))
+}
+
+/// The columns of a log, where logs without a period leave the times and breaks empty,
+/// a period ending on a later day tells how many days later, such as `02:00+1`, and
+/// absences give their kind, such as `vacation` or `sick half` for half a day
+fn csv_row(log: &WorkLog) -> [String; 8] {
+    let (start, end, breaks) = match log.get_period() {
+        Some((period, breaks)) => {
+            let (end, days) = period.end();
+            let end = match days {
+                0 => end.to_string(),
+                days => format!("{}+{}", end, days),
+            };
+            (period.start().to_string(), end, breaks.to_m().to_string())
+        }
+        None => (String::new(), String::new(), String::new()),
+    };
+    let absence = match log.get_absence() {
+        Some((kind, half_day)) => {
+            let name = kind.to_possible_value().expect("No absence is skipped");
+            let half = if half_day { " half" } else { "" };
+            format!("{}{}", name.get_name(), half)
+        }
+        None => String::new(),
+    };
+    let duration = log.get_duration();
+
+    [
+        log.get_date().to_string(),
+        start,
+        end,
+        breaks,
+        duration.to_m().to_string(),
+        decimal_hours(&duration),
+        absence,
+        log.get_label(),
+    ]
+}
+
+fn decimal_hours(duration: &Duration) -> String {
+    format!("{:.2}", duration.to_m() as f64 / 60.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project_log::{read_import_file, Absence, Break, ImportFormat};
+    use crate::time::{Date, Period, Time};
+
+    fn logs() -> Vec<WorkLog> {
+        let period = |from: (usize, usize), to: (usize, usize), days: usize| {
+            Period::spanning(Time::new(from.0, from.1), Time::new(to.0, to.1), days)
+        };
+        vec![
+            WorkLog::new_period(
+                period((8, 15), (16, 40), 0),
+                Date::new(2023, 4, 3),
+                "Fixed \"the\" build, at last".to_owned(),
+                vec![Break::Duration(Duration::from_m(30))],
+                None,
+            ),
+            WorkLog::new_duration(
+                Duration::from_hm(3, 0),
+                Date::new(2023, 4, 4),
+                "Travel".to_owned(),
+            ),
+            WorkLog::new_period(
+                period((22, 0), (2, 0), 1),
+                Date::new(2023, 4, 5),
+                "Release".to_owned(),
+                vec![],
+                None,
+            ),
+            WorkLog::new_period(
+                period((8, 0), (10, 0), 2),
+                Date::new(2023, 4, 6),
+                "On call".to_owned(),
+                vec![],
+                None,
+            ),
+            WorkLog::new_absence(Absence::Vacation, Date::new(2023, 4, 10), true, None),
+            WorkLog::new_absence(
+                Absence::Sick,
+                Date::new(2023, 4, 11),
+                false,
+                Some("Flu".to_owned()),
+            ),
+        ]
+    }
+
+    fn export(logs: &[WorkLog]) -> String {
+        let mut out = vec![];
+        let logs: Vec<&WorkLog> = logs.iter().collect();
+        ExportFormat::Csv.write(&logs, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_rows() {
+        assert_eq!(
+            export(&logs()),
+            "date,start,end,break_minutes,minutes,hours,absence,description\n\
+             2023-04-03,08:15,16:40,30,475,7.92,,\"Fixed \"\"the\"\" build, at last\"\n\
+             2023-04-04,,,,180,3.00,,Travel\n\
+             2023-04-05,22:00,02:00+1,0,240,4.00,,Release\n\
+             2023-04-06,08:00,10:00+2,0,3000,50.00,,On call\n\
+             2023-04-10,,,,0,0.00,vacation half,\n\
+             2023-04-11,,,,0,0.00,sick,Flu\n"
+        );
+    }
+
+    #[test]
+    fn csv_reads_back_through_import() {
+        let path = std::env::temp_dir().join(format!("flex-cli-export-{}.csv", std::process::id()));
+        std::fs::write(&path, export(&logs())).unwrap();
+        let imported = read_import_file(&path, ImportFormat::Csv, &[], "Work");
+        std::fs::remove_file(&path).unwrap();
+
+        let imported = imported.unwrap();
+        assert_eq!(imported.len(), logs().len());
+        for (log, expected) in imported.iter().zip(logs()) {
+            assert!(log.same_work(&expected), "{} is not {}", log, expected);
+            assert_eq!(log.get_label(), expected.get_label());
+            assert_eq!(log.get_duration(), expected.get_duration());
+            assert_eq!(log.get_absence(), expected.get_absence());
+            assert_eq!(csv_row(log), csv_row(&expected));
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::error::{Error, Result};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Held while changing a project, so that two commands can not change it at the same time.
+/// The lock is released when dropped.
+#[derive(Debug)]
+pub struct ProjectLock {
+    _file: File,
+}
+
+impl ProjectLock {
+    /// Waits until no one else is changing the project
+    pub fn acquire(project_name: &str) -> Result<Self> {
+        super::folders::create_folders()?;
+        Self::at(&Self::path(project_name)?)
+    }
+
+    /// Removes the lock file of a project which no longer exists under that name
+    pub fn remove(project_name: &str) -> Result<()> {
+        let path = Self::path(project_name)?;
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(Error::io(&path, err)),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(project_name: &str) -> Result<PathBuf> {
+        let mut path = super::folders::log_folder()?;
+        path.push(format!("{}.lock", project_name));
+        Ok(path)
+    }
+
+    /// Waits until no one else holds the lock in a file
+    pub fn at(path: &Path) -> Result<Self> {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .map_err(|err| Error::io(path, err))?;
+        file.lock().map_err(|err| Error::io(path, err))?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// Replaces the content of a file without ever leaving it half written, keeping the
+/// old content in a `.bak` file next to it.
+pub fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let temp_path = with_suffix(path, ".tmp");
+    let mut temp = File::create(&temp_path).map_err(|err| Error::io(&temp_path, err))?;
+    temp.write_all(content.as_bytes())
+        .and_then(|_| temp.sync_all())
+        .map_err(|err| Error::io(&temp_path, err))?;
+
+    if path.exists() {
+        let backup_path = with_suffix(path, ".bak");
+        fs::copy(path, &backup_path).map_err(|err| Error::io(&backup_path, err))?;
+    }
+    fs::rename(&temp_path, path).map_err(|err| Error::io(path, err))?;
+
+    // Make the rename itself survive a crash, which is not possible on all platforms
+    if let Some(folder) = path.parent() {
+        let _ = File::open(folder).and_then(|folder| folder.sync_all());
+    }
+    Ok(())
+}
+
+/// The path with a suffix added to the file name, such as `work.json.bak`
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
@@ -0,0 +1,523 @@
+use clap::ValueEnum;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{migrations, Absence, Break, WorkLog};
+use crate::error::{Error, Result};
+use crate::parser::{self, clock_time, parse_iso_date, parse_time, period_between};
+use crate::time::{Date, Duration, Time};
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+
+/// File formats logs can be imported from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// CSV with the columns written by `export`, or the ones given with --column
+    Csv,
+    /// The detailed CSV export of Toggl Track
+    Toggl,
+    /// The detailed CSV export of Clockify
+    Clockify,
+    /// A project saved as JSON by flex-cli, or a list of its logs
+    Json,
+}
+
+/// What a column of a CSV file holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Field {
+    /// The date the work started
+    Date,
+    /// Time the work started, such as 08:15 or 8:15:00 AM
+    Start,
+    /// Time the work ended, with +N if it was N days later
+    End,
+    /// Date the work ended, if not the date it started
+    EndDate,
+    /// Length of the breaks, as hours:minutes or whole minutes
+    Breaks,
+    /// Time worked without a start and end, as hours:minutes[:seconds] or decimal hours
+    Duration,
+    /// Kind of absence for days away from work, such as vacation or `sick half` for half a day
+    Absence,
+    Description,
+}
+
+impl ImportFormat {
+    /// The format of a file, judged by its extension
+    pub fn of_file(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ImportFormat::Json,
+            _ => ImportFormat::Csv,
+        }
+    }
+
+    /// The column holding each field, by its header
+    fn columns(&self) -> Vec<(Field, &'static str)> {
+        match self {
+            ImportFormat::Json => vec![],
+            ImportFormat::Csv => vec![
+                (Field::Date, "date"),
+                (Field::Start, "start"),
+                (Field::End, "end"),
+                (Field::Breaks, "break_minutes"),
+                (Field::Duration, "hours"),
+                (Field::Absence, "absence"),
+                (Field::Description, "description"),
+            ],
+            ImportFormat::Toggl => vec![
+                (Field::Date, "Start date"),
+                (Field::Start, "Start time"),
+                (Field::End, "End time"),
+                (Field::EndDate, "End date"),
+                (Field::Duration, "Duration"),
+                (Field::Description, "Description"),
+            ],
+            ImportFormat::Clockify => vec![
+                (Field::Date, "Start Date"),
+                (Field::Start, "Start Time"),
+                (Field::End, "End Time"),
+                (Field::EndDate, "End Date"),
+                (Field::Duration, "Duration (h)"),
+                (Field::Description, "Description"),
+            ],
+        }
+    }
+}
+
+/// Reads the logs of a file exported from flex-cli or another time tracker, where `columns`
+/// replaces the headers of the fields in CSV files and logs without a description get
+/// `default_description`.
+pub fn read_import_file(
+    path: &Path,
+    format: ImportFormat,
+    columns: &[(Field, String)],
+    default_description: &str,
+) -> Result<Vec<WorkLog>> {
+    let content = std::fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+    let corrupt = |reason: String| Error::Corrupt {
+        path: path.to_owned(),
+        reason,
+    };
+
+    if format == ImportFormat::Json {
+        return read_json(&content).map_err(corrupt);
+    }
+
+    let mut headers: Vec<(Field, String, bool)> = format
+        .columns()
+        .into_iter()
+        .map(|(field, header)| (field, header.to_owned(), false))
+        .collect();
+    for (field, header) in columns {
+        headers.retain(|(other, _, _)| other != field);
+        headers.push((*field, header.clone(), true));
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let file_headers: Vec<String> = reader
+        .headers()
+        .map_err(|err| corrupt(err.to_string()))?
+        .iter()
+        .map(|header| header.trim_start_matches('\u{feff}').to_lowercase())
+        .collect();
+
+    let mut indices = vec![];
+    for (field, header, given) in headers {
+        match file_headers
+            .iter()
+            .position(|h| *h == header.to_lowercase())
+        {
+            Some(index) => indices.push((field, index)),
+            None if given || field == Field::Date => {
+                return Err(Error::InvalidInput(format!(
+                    "The file has no column '{}', choose the column of the {} with --column",
+                    header, field
+                )))
+            }
+            None => {}
+        }
+    }
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|err| corrupt(err.to_string()))?;
+            let line = record.position().map_or(0, |position| position.line());
+            let value = |field: Field| {
+                let (_, index) = indices.iter().find(|(other, _)| *other == field)?;
+                record.get(*index).filter(|value| !value.is_empty())
+            };
+            read_row(value, default_description)
+                .map_err(|reason| Error::InvalidInput(format!("{} on line {}", reason, line)))
+        })
+        .collect()
+}
+
+/// Reads a project file, upgrading it if it is old, or a list of logs
+fn read_json(content: &str) -> std::result::Result<Vec<WorkLog>, String> {
+    let mut value: serde_json::Value =
+        serde_json::from_str(content).map_err(|err| err.to_string())?;
+    if value.is_array() {
+        value = serde_json::json!({ "logs": value });
+    }
+    migrations::migrate(&mut value)?;
+
+    let logs = value.get_mut("logs").ok_or("missing logs")?.take();
+    serde_json::from_value(logs).map_err(|err| err.to_string())
+}
+
+fn read_row<'a>(
+    value: impl Fn(Field) -> Option<&'a str>,
+    default_description: &str,
+) -> std::result::Result<WorkLog, String> {
+    let invalid = |field: Field| format!("Invalid {} '{}'", field, value(field).unwrap_or(""));
+
+    let date_str = value(Field::Date).ok_or("Missing date")?;
+    let date = parse_tracker_date(date_str).ok_or_else(|| invalid(Field::Date))?;
+    if let Some(absence) = value(Field::Absence) {
+        let (kind, half_day) = parse_absence(absence).ok_or_else(|| invalid(Field::Absence))?;
+        let description = value(Field::Description).map(str::to_owned);
+        return Ok(WorkLog::new_absence(kind, date, half_day, description));
+    }
+    let description = value(Field::Description)
+        .unwrap_or(default_description)
+        .to_owned();
+
+    match (
+        value(Field::Start),
+        value(Field::End),
+        value(Field::Duration),
+    ) {
+        (Some(start), Some(end), _) => {
+            let from = parse_tracker_time(start).ok_or_else(|| invalid(Field::Start))?;
+            let (to, days) = parse_tracker_end(end).ok_or_else(|| invalid(Field::End))?;
+            let days = match value(Field::EndDate) {
+                Some(end_date) => {
+                    let end_date =
+                        parse_tracker_date(end_date).ok_or_else(|| invalid(Field::EndDate))?;
+                    let days = usize::try_from(end_date.days_since(&date))
+                        .map_err(|_| invalid(Field::EndDate))?;
+                    Some(days)
+                }
+                None => days,
+            };
+            let breaks = value(Field::Breaks)
+                .map(|breaks| parse_tracker_breaks(breaks).ok_or_else(|| invalid(Field::Breaks)))
+                .transpose()?
+                .filter(|breaks| breaks.to_m() > 0)
+                .map(Break::Duration)
+                .into_iter()
+                .collect();
+            let period = period_between(from, to, days).map_err(|err| err.to_string())?;
+            Ok(WorkLog::new_period(period, date, description, breaks, None))
+        }
+        (_, _, Some(duration)) => {
+            let duration =
+                parse_tracker_duration(duration).ok_or_else(|| invalid(Field::Duration))?;
+            Ok(WorkLog::new_duration(duration, date, description))
+        }
+        _ => Err("Missing either a start and end or a duration".to_owned()),
+    }
+}
+
+/// Parses YYYY-MM-DD, YYYY/MM/DD, MM/DD/YYYY or DD.MM.YYYY, ignoring any time after it
+fn parse_tracker_date(date_str: &str) -> Option<Date> {
+    lazy_static! {
+        static ref US_RE: Regex = Regex::new(r"^(\d\d?)/(\d\d?)/(\d{4})$").unwrap();
+        static ref EUROPEAN_RE: Regex = Regex::new(r"^(\d\d?)\.(\d\d?)\.(\d{4})$").unwrap();
+    }
+
+    let date_str = date_str.split(['T', ' ']).next()?;
+    if let Ok(date) = parse_iso_date(&date_str.replace('/', "-")) {
+        return Some(date);
+    }
+
+    let number = |caps: &regex::Captures, index: usize| caps[index].parse::<usize>().ok();
+    let date = if let Some(caps) = US_RE.captures(date_str) {
+        Date::new(number(&caps, 3)?, number(&caps, 1)?, number(&caps, 2)?)
+    } else {
+        let caps = EUROPEAN_RE.captures(date_str)?;
+        Date::new(number(&caps, 3)?, number(&caps, 2)?, number(&caps, 1)?)
+    };
+    date.is_valid().then_some(date)
+}
+
+/// Parses hours:minutes[:seconds] [AM|PM], dropping the seconds
+fn parse_tracker_time(time_str: &str) -> Option<Time> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(\d\d?:\d\d)(?::\d\d)?\s*(?i:(am|pm))?$").unwrap();
+    }
+
+    let caps = RE.captures(time_str)?;
+    let (mut hours, minutes) = parse_time(&caps[1]).ok()?;
+    if let Some(half) = caps.get(2) {
+        if !(1..=12).contains(&hours) {
+            return None;
+        }
+        hours = hours % 12
+            + if half.as_str().eq_ignore_ascii_case("pm") {
+                12
+            } else {
+                0
+            };
+    }
+    clock_time(hours, minutes)
+}
+
+/// Parses an end time, along with how many days later it is if it ends with +N
+fn parse_tracker_end(end_str: &str) -> Option<(Time, Option<usize>)> {
+    match end_str.split_once('+') {
+        Some((time, days)) => Some((parse_tracker_time(time)?, Some(days.parse().ok()?))),
+        None => Some((parse_tracker_time(end_str)?, None)),
+    }
+}
+
+/// Parses hours:minutes[:seconds] or decimal hours, rounded to whole minutes
+fn parse_tracker_duration(duration_str: &str) -> Option<Duration> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(\d+:\d\d)(?::(\d\d))?$").unwrap();
+    }
+
+    if let Some(caps) = RE.captures(duration_str) {
+        let duration = parser::parse_duration(&caps[1]).ok()?;
+        let seconds: i32 = caps.get(2).map_or(Some(0), |s| s.as_str().parse().ok())?;
+        return (seconds < 60).then(|| duration + Duration::from_m((seconds + 30) / 60));
+    }
+
+    let hours: f64 = duration_str.replace(',', ".").parse().ok()?;
+    (hours.is_finite() && hours >= 0.0).then(|| Duration::from_m((hours * 60.0).round() as i32))
+}
+
+/// Parses the kind of an absence, followed by `half` if only for half the day
+fn parse_absence(absence_str: &str) -> Option<(Absence, bool)> {
+    let mut words = absence_str.split_whitespace();
+    let kind = Absence::from_str(words.next()?, true).ok()?;
+    let half_day = match words.next() {
+        None => false,
+        Some(word) if word.eq_ignore_ascii_case("half") => true,
+        Some(_) => return None,
+    };
+    words.next().is_none().then_some((kind, half_day))
+}
+
+/// Parses hours:minutes or whole minutes
+fn parse_tracker_breaks(breaks_str: &str) -> Option<Duration> {
+    match breaks_str.parse::<i32>() {
+        Ok(minutes) if minutes >= 0 => Some(Duration::from_m(minutes)),
+        Ok(_) => None,
+        Err(_) => parse_tracker_duration(breaks_str).filter(|_| breaks_str.contains(':')),
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = self.to_possible_value().expect("No field is skipped");
+        write!(f, "{}", name.get_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::Period;
+
+    fn read(
+        name: &str,
+        content: &str,
+        format: ImportFormat,
+        columns: &[(Field, String)],
+    ) -> Vec<WorkLog> {
+        let path =
+            std::env::temp_dir().join(format!("flex-cli-import-{}-{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        let logs = read_import_file(&path, format, columns, "Work");
+        std::fs::remove_file(&path).unwrap();
+        logs.unwrap()
+    }
+
+    fn period(date: Date, from: (usize, usize), to: (usize, usize), days: usize) -> WorkLog {
+        let period = Period::spanning(Time::new(from.0, from.1), Time::new(to.0, to.1), days);
+        WorkLog::new_period(period, date, "Work".to_owned(), vec![], None)
+    }
+
+    fn assert_same_work(logs: &[WorkLog], expected: &[WorkLog]) {
+        assert_eq!(logs.len(), expected.len());
+        for (log, expected) in logs.iter().zip(expected) {
+            assert!(log.same_work(expected), "{} is not {}", log, expected);
+        }
+    }
+
+    #[test]
+    fn csv_written_by_export() {
+        let logs = read(
+            "export.csv",
+            "date,start,end,break_minutes,minutes,hours,absence,description\n\
+             2023-04-03,08:15,16:40,30,475,7.92,,\"Fixed the build, at last\"\n\
+             2023-04-04,,,,180,3.00,,Travel\n\
+             2023-04-05,22:00,02:00+1,0,240,4.00,,\n\
+             2023-04-06,,,,0,0.00,vacation half,\n",
+            ImportFormat::Csv,
+            &[],
+        );
+
+        assert_same_work(
+            &logs,
+            &[
+                period(Date::new(2023, 4, 3), (8, 15), (16, 40), 0),
+                WorkLog::new_duration(
+                    Duration::from_hm(3, 0),
+                    Date::new(2023, 4, 4),
+                    "Travel".to_owned(),
+                ),
+                period(Date::new(2023, 4, 5), (22, 0), (2, 0), 1),
+                WorkLog::new_absence(Absence::Vacation, Date::new(2023, 4, 6), true, None),
+            ],
+        );
+        assert_eq!(logs[0].get_duration(), Duration::from_m(475));
+        assert_eq!(logs[0].get_label(), "Fixed the build, at last");
+        assert_eq!(logs[2].get_label(), "Work");
+        assert_eq!(logs[3].get_absence(), Some((Absence::Vacation, true)));
+    }
+
+    #[test]
+    fn csv_with_columns_given() {
+        let columns = [
+            (Field::Date, "Day".to_owned()),
+            (Field::Duration, "Hours".to_owned()),
+            (Field::Description, "Note".to_owned()),
+        ];
+        let logs = read(
+            "columns.csv",
+            "Day,Hours,Note\n31.12.2023,\"7,5\",Closing\n2024-01-02,1:30,\n",
+            ImportFormat::Csv,
+            &columns,
+        );
+
+        assert_same_work(
+            &logs,
+            &[
+                WorkLog::new_duration(
+                    Duration::from_hm(7, 30),
+                    Date::new(2023, 12, 31),
+                    "Closing".to_owned(),
+                ),
+                WorkLog::new_duration(
+                    Duration::from_hm(1, 30),
+                    Date::new(2024, 1, 2),
+                    "Work".to_owned(),
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn toggl_export() {
+        let logs = read(
+            "toggl.csv",
+            "User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags\n\
+             Kim,kim@example.com,,Site,,Design,No,2024-06-03,08:00:00,2024-06-03,11:45:00,03:45:00,\n\
+             Kim,kim@example.com,,Site,,On call,No,2024-06-03,20:00:00,2024-06-05,08:00:00,36:00:00,\n",
+            ImportFormat::Toggl,
+            &[],
+        );
+
+        assert_same_work(
+            &logs,
+            &[
+                period(Date::new(2024, 6, 3), (8, 0), (11, 45), 0),
+                period(Date::new(2024, 6, 3), (20, 0), (8, 0), 2),
+            ],
+        );
+        assert_eq!(logs[1].get_duration(), Duration::from_hm(36, 0));
+    }
+
+    #[test]
+    fn clockify_export() {
+        let logs = read(
+            "clockify.csv",
+            "\u{feff}\"Project\",\"Client\",\"Description\",\"Task\",\"User\",\"Start Date\",\"Start Time\",\"End Date\",\"End Time\",\"Duration (h)\",\"Duration (decimal)\"\n\
+             \"Site\",\"\",\"Review\",\"\",\"Kim\",\"06/03/2024\",\"08:30:00 AM\",\"06/03/2024\",\"12:15:00 PM\",\"03:45:00\",\"3.75\"\n\
+             \"Site\",\"\",\"\",\"\",\"Kim\",\"06/03/2024\",\"10:00:00 PM\",\"06/04/2024\",\"01:00:00 AM\",\"03:00:00\",\"3.00\"\n",
+            ImportFormat::Clockify,
+            &[],
+        );
+
+        assert_same_work(
+            &logs,
+            &[
+                period(Date::new(2024, 6, 3), (8, 30), (12, 15), 0),
+                period(Date::new(2024, 6, 3), (22, 0), (1, 0), 1),
+            ],
+        );
+        assert_eq!(logs[1].get_label(), "Work");
+    }
+
+    #[test]
+    fn json_project_or_list_of_logs() {
+        let old_project = r#"{"logs": [{"Period": {
+            "period": {"from": {"hours": 22, "minutes": 0}, "to": {"hours": 6, "minutes": 0}},
+            "duration": {"minutes": -960}, "breaks": [],
+            "date": {"year": 2022, "month": 11, "day": 7}, "description": "Night"}}]}"#;
+        let logs = read("project.json", old_project, ImportFormat::Json, &[]);
+        assert_same_work(&logs, &[period(Date::new(2022, 11, 7), (22, 0), (6, 0), 1)]);
+
+        let list = r#"[{"Duration": {"duration": {"minutes": 90},
+            "date": {"year": 2022, "month": 11, "day": 8}, "description": null}}]"#;
+        let logs = read("logs.json", list, ImportFormat::Json, &[]);
+        assert_same_work(
+            &logs,
+            &[WorkLog::new_duration(
+                Duration::from_m(90),
+                Date::new(2022, 11, 8),
+                "Work".to_owned(),
+            )],
+        );
+    }
+
+    #[test]
+    fn same_work_ignores_descriptions_and_ids() {
+        let date = Date::new(2024, 6, 3);
+        let mut logged = period(date.clone(), (8, 0), (12, 0), 0);
+        logged.set_id(7);
+        let imported = WorkLog::new_period(
+            Period::new(Time::new(8, 0), Time::new(12, 0)),
+            date.clone(),
+            "Something else".to_owned(),
+            vec![],
+            None,
+        );
+
+        assert!(imported.same_work(&logged));
+        assert!(!period(date.clone(), (8, 0), (12, 30), 0).same_work(&logged));
+        assert!(!period(date.add_days(1), (8, 0), (12, 0), 0).same_work(&logged));
+        assert!(
+            !WorkLog::new_duration(Duration::from_hm(4, 0), date, "Work".to_owned())
+                .same_work(&logged)
+        );
+    }
+
+    #[test]
+    fn invalid_rows_tell_the_line() {
+        let path = std::env::temp_dir().join(format!(
+            "flex-cli-import-{}-invalid.csv",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "date,start,end\n2024-06-03,8:00,12:00\n2024-06-04,20:00,10:00\n",
+        )
+        .unwrap();
+        let result = read_import_file(&path, ImportFormat::Csv, &[], "Work");
+        std::fs::remove_file(&path).unwrap();
+
+        match result {
+            Err(Error::InvalidInput(reason)) => {
+                assert!(reason.ends_with("on line 3"), "{}", reason)
+            }
+            other => panic!("expected an invalid row, got {:?}", other),
+        }
+    }
+}
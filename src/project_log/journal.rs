@@ -0,0 +1,532 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::migrations::CURRENT_VERSION;
+use super::storage::Storage;
+use super::{ProjectLog, WorkLog};
+use crate::error::{Error, Result};
+use crate::time::{now, Date, Time};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+
+/// Most changes kept in the journal of a project, and so the most which can be undone.
+/// The oldest are dropped once there are twice as many, to not rewrite the journal on
+/// every change.
+const KEPT_ENTRIES: usize = 100;
+
+/// One change to a project, saved with only what is needed to revert it
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JournalEntry {
+    date: Date,
+    time: Time,
+    command: String,
+    /// Number of earlier changes this entry reverted, if it was an undo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    undid: Option<usize>,
+    /// Version of the project format when changed, as the change is kept in that format
+    #[serde(default)]
+    version: usize,
+    /// True if the project did not exist before the change
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    created: bool,
+    /// Everything but the logs of the project before the change, if that changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    settings: Option<Map<String, Value>>,
+    /// The logs changed or removed, as they were before along with where they were
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    logs: Vec<(usize, WorkLog)>,
+    /// Ids of the logs added
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    added: Vec<usize>,
+    /// Name of the project before it was renamed, if that was the change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    renamed_from: Option<String>,
+}
+
+/// Appends the current command to the journal of a project, along with what it changed
+/// in the project, where None is a project which does not exist.
+pub fn record(
+    storage: &dyn Storage,
+    project: &str,
+    before: Option<ProjectLog>,
+    after: Option<&ProjectLog>,
+) -> Result<()> {
+    let mut entry = JournalEntry::new(None);
+    let after_logs: BTreeMap<usize, Value> = after
+        .iter()
+        .flat_map(|after| &after.logs)
+        .map(|log| (log.get_id(), to_value(log)))
+        .collect();
+
+    match before {
+        None => entry.created = true,
+        Some(before) => {
+            let before_settings = settings(&before);
+            if after.is_none_or(|after| settings(after) != before_settings) {
+                entry.settings = Some(before_settings);
+            }
+            let before_ids: BTreeSet<usize> = before.logs.iter().map(WorkLog::get_id).collect();
+            entry.added = after_logs
+                .keys()
+                .filter(|id| !before_ids.contains(id))
+                .copied()
+                .collect();
+            entry.logs = before
+                .logs
+                .into_iter()
+                .enumerate()
+                .filter(|(_, log)| after_logs.get(&log.get_id()) != Some(&to_value(log)))
+                .collect();
+        }
+    }
+    append(storage, project, entry)
+}
+
+/// Appends the current command to the journal of a project which was renamed, which
+/// has to be moved to the new name first.
+pub fn record_rename(storage: &dyn Storage, project: &str, old_name: &str) -> Result<()> {
+    let mut entry = JournalEntry::new(None);
+    entry.renamed_from = Some(old_name.to_owned());
+    append(storage, project, entry)
+}
+
+/// Reverts the latest changes which are not already undone, returning them along with
+/// the project as it was before them, or None if it did not exist.
+pub fn undo(
+    storage: &dyn Storage,
+    project: &str,
+    count: usize,
+    current: Option<ProjectLog>,
+) -> Result<(Vec<JournalEntry>, Option<ProjectLog>)> {
+    let entries = read(storage, project)?;
+    let mut active = active_indices(&entries);
+    if count == 0 || count > active.len() {
+        return Err(Error::InvalidState(format!(
+            "There are only {} changes to undo",
+            active.len()
+        )));
+    }
+
+    let undone_indices = active.split_off(active.len() - count);
+    let mut undone: Vec<JournalEntry> = entries
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| undone_indices.contains(index))
+        .map(|(_, entry)| entry)
+        .rev()
+        .collect();
+    if let Some(old_name) = undone.iter().find_map(|entry| entry.renamed_from.as_ref()) {
+        return Err(Error::InvalidState(format!(
+            "Renames can not be undone, use `rename {} {}` to change the name back",
+            project, old_name
+        )));
+    }
+    if undone.iter().any(|entry| entry.version != CURRENT_VERSION) {
+        return Err(Error::InvalidState(
+            "Changes saved by an older version of flex-cli can not be undone".to_owned(),
+        ));
+    }
+
+    // Each change is reverted from the latest, as it only knows the project right after it
+    let mut reverted = current;
+    for entry in undone.iter_mut() {
+        reverted = entry
+            .revert(project, reverted)
+            .map_err(|reason| Error::Corrupt {
+                path: storage.location(project),
+                reason: format!("the journal does not match the project, {}", reason),
+            })?;
+    }
+
+    append(storage, project, JournalEntry::new(Some(count)))?;
+    Ok((undone, reverted))
+}
+
+/// The latest entries, oldest first, each with a flag for if it has been undone
+pub fn history(
+    storage: &dyn Storage,
+    project: &str,
+    count: usize,
+) -> Result<Vec<(JournalEntry, bool)>> {
+    let entries = read(storage, project)?;
+    let active = active_indices(&entries);
+    let skip = entries.len().saturating_sub(count);
+
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .skip(skip)
+        .map(|(index, entry)| {
+            let undone = entry.undid.is_none() && !active.contains(&index);
+            (entry, undone)
+        })
+        .collect())
+}
+
+/// Indices of the changes which have not been undone, in order
+fn active_indices(entries: &[JournalEntry]) -> Vec<usize> {
+    let mut active = vec![];
+    for (index, entry) in entries.iter().enumerate() {
+        match entry.undid {
+            Some(count) => active.truncate(active.len().saturating_sub(count)),
+            None => active.push(index),
+        }
+    }
+    active
+}
+
+fn read(storage: &dyn Storage, project: &str) -> Result<Vec<JournalEntry>> {
+    storage
+        .journal(project)?
+        .iter()
+        .map(|line| {
+            serde_json::from_str(line).map_err(|err| Error::Corrupt {
+                path: storage.location(project),
+                reason: format!("invalid journal entry: {}", err),
+            })
+        })
+        .collect()
+}
+
+fn append(storage: &dyn Storage, project: &str, entry: JournalEntry) -> Result<()> {
+    let line = serde_json::to_string(&entry).expect("Failed to serialize journal entry.");
+    storage.append_journal(project, &line)?;
+
+    if storage.journal_len(project)? > 2 * KEPT_ENTRIES {
+        storage.prune_journal(project, KEPT_ENTRIES)?;
+    }
+    Ok(())
+}
+
+/// Everything but the logs of a project, leaving out what a change can not revert
+fn settings(project: &ProjectLog) -> Map<String, Value> {
+    let Value::Object(mut settings) = to_value(project) else {
+        unreachable!("Projects are serialized as objects")
+    };
+    // The storage decides the name, and ids are never given out again
+    for key in ["name", "logs", "next_id"] {
+        settings.remove(key);
+    }
+    settings
+}
+
+fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).expect("Failed to serialize project log.")
+}
+
+impl JournalEntry {
+    fn new(undid: Option<usize>) -> Self {
+        let (date, time) = now();
+        let command = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+        Self {
+            date,
+            time,
+            command,
+            undid,
+            version: CURRENT_VERSION,
+            created: false,
+            settings: None,
+            logs: vec![],
+            added: vec![],
+            renamed_from: None,
+        }
+    }
+
+    /// The project as it was before this change, given the project right after it
+    fn revert(
+        &mut self,
+        name: &str,
+        current: Option<ProjectLog>,
+    ) -> std::result::Result<Option<ProjectLog>, String> {
+        if self.created {
+            return Ok(None);
+        }
+
+        let mut project = match (self.settings.clone(), current) {
+            (Some(mut settings), current) => {
+                settings.insert("name".to_owned(), name.into());
+                settings.insert("logs".to_owned(), Value::Array(vec![]));
+                let mut project: ProjectLog = serde_json::from_value(Value::Object(settings))
+                    .map_err(|err| err.to_string())?;
+                if let Some(current) = current {
+                    project.logs = current.logs;
+                    project.next_id = current.next_id;
+                }
+                project
+            }
+            (None, Some(current)) => current,
+            (None, None) => return Err("the project does not exist".to_owned()),
+        };
+
+        let changed: BTreeSet<usize> = self
+            .logs
+            .iter()
+            .map(|(_, log)| log.get_id())
+            .chain(self.added.iter().copied())
+            .collect();
+        project.logs.retain(|log| !changed.contains(&log.get_id()));
+        for (index, log) in std::mem::take(&mut self.logs) {
+            let index = index.min(project.logs.len());
+            project.logs.insert(index, log);
+        }
+        project.assign_missing_ids();
+        Ok(Some(project))
+    }
+}
+
+impl Display for JournalEntry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} {}: {}", self.date, self.time, self.command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project_log::StorageKind;
+    use crate::time::Duration;
+    use serde_json::json;
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+
+    /// Keeps only the journal, which is all the journal uses of a storage
+    #[derive(Default)]
+    struct MemoryStorage {
+        journal: RefCell<Vec<String>>,
+    }
+
+    impl Storage for MemoryStorage {
+        fn kind(&self) -> StorageKind {
+            StorageKind::Json
+        }
+
+        fn names(&self) -> Result<Vec<String>> {
+            unreachable!()
+        }
+
+        fn load(&self, _project: &str) -> Result<Option<String>> {
+            unreachable!()
+        }
+
+        fn store(&self, _project: &str, _content: &str) -> Result<()> {
+            unreachable!()
+        }
+
+        fn remove(&self, _project: &str) -> Result<()> {
+            unreachable!()
+        }
+
+        fn rename(&self, _project: &str, _new_name: &str) -> Result<()> {
+            unreachable!()
+        }
+
+        fn journal(&self, _project: &str) -> Result<Vec<String>> {
+            Ok(self.journal.borrow().clone())
+        }
+
+        fn append_journal(&self, _project: &str, line: &str) -> Result<()> {
+            self.journal.borrow_mut().push(line.to_owned());
+            Ok(())
+        }
+
+        fn prune_journal(&self, _project: &str, keep: usize) -> Result<()> {
+            let mut journal = self.journal.borrow_mut();
+            let dropped = journal.len().saturating_sub(keep);
+            journal.drain(..dropped);
+            Ok(())
+        }
+
+        fn location(&self, project: &str) -> PathBuf {
+            PathBuf::from(project)
+        }
+
+        fn retire(&self) -> Result<PathBuf> {
+            unreachable!()
+        }
+    }
+
+    /// A project and its journal, changed like the commands do
+    #[derive(Default)]
+    struct Changes {
+        storage: MemoryStorage,
+        project: Option<ProjectLog>,
+    }
+
+    impl Changes {
+        fn change(&mut self, change: impl FnOnce(&mut ProjectLog)) {
+            let before = self.project.as_ref().map(copy);
+            let mut after = self.project.as_ref().map_or_else(new_project, copy);
+            change(&mut after);
+            record(&self.storage, "work", before, Some(&after)).unwrap();
+            self.project = Some(after);
+        }
+
+        fn log(&mut self, hours: usize) {
+            self.change(|project| {
+                let date = Date::new(2024, 1, hours);
+                let log = WorkLog::new_duration(
+                    Duration::from_hm(hours as i32, 0),
+                    date,
+                    "Work".to_owned(),
+                );
+                project.insert_logs(vec![log]).unwrap();
+            });
+        }
+
+        fn undo(&mut self, count: usize) -> Result<Vec<String>> {
+            let current = self.project.as_ref().map(copy);
+            let (undone, before) = undo(&self.storage, "work", count, current)?;
+            self.project = before;
+            Ok(undone.iter().map(|entry| entry.command.clone()).collect())
+        }
+
+        /// Ids and hours of the logs, in order
+        fn logs(&self) -> Vec<(usize, i32)> {
+            let project = self.project.as_ref().expect("The project exists");
+            project
+                .logs
+                .iter()
+                .map(|log| (log.get_id(), log.get_duration().to_m() / 60))
+                .collect()
+        }
+    }
+
+    fn new_project() -> ProjectLog {
+        serde_json::from_value(json!({
+            "version": CURRENT_VERSION,
+            "name": "work",
+            "logs": [],
+            "next_id": 1,
+            "start_date": { "year": 2024, "month": 1, "day": 1 },
+        }))
+        .unwrap()
+    }
+
+    fn copy(project: &ProjectLog) -> ProjectLog {
+        serde_json::from_str(&project.serialize()).unwrap()
+    }
+
+    #[test]
+    fn undo_the_latest_changes() {
+        let mut changes = Changes::default();
+        changes.change(|_| {});
+        for hours in 1..=3 {
+            changes.log(hours);
+        }
+        changes.change(|project| {
+            let mut log = WorkLog::new_duration(
+                Duration::from_hm(5, 0),
+                Date::new(2024, 1, 2),
+                "Work".to_owned(),
+            );
+            log.set_id(2);
+            project.logs[1] = log;
+        });
+        changes.change(|project| {
+            project.logs.remove(0);
+        });
+        assert_eq!(changes.logs(), vec![(2, 5), (3, 3)]);
+
+        changes.undo(2).unwrap();
+        assert_eq!(changes.logs(), vec![(1, 1), (2, 2), (3, 3)]);
+        // Undone changes are skipped when undoing again
+        changes.undo(1).unwrap();
+        assert_eq!(changes.logs(), vec![(1, 1), (2, 2)]);
+
+        // Ids of undone logs are not given out again
+        changes.log(4);
+        assert_eq!(changes.logs(), vec![(1, 1), (2, 2), (4, 4)]);
+
+        changes.undo(2).unwrap();
+        assert_eq!(changes.logs(), vec![(1, 1)]);
+        changes.undo(2).unwrap();
+        assert!(changes.project.is_none());
+        assert!(matches!(changes.undo(1), Err(Error::InvalidState(_))));
+    }
+
+    #[test]
+    fn undo_settings_and_removed_projects() {
+        let mut changes = Changes::default();
+        changes.change(|_| {});
+        changes.log(8);
+        changes.change(|project| project.max_per_day = Duration::from_hm(10, 0));
+
+        let before = changes.project.take();
+        record(&changes.storage, "work", before, None).unwrap();
+        changes.undo(1).unwrap();
+        assert_eq!(changes.logs(), vec![(1, 8)]);
+        let project = changes.project.as_ref().unwrap();
+        assert_eq!(project.max_per_day, Duration::from_hm(10, 0));
+        assert_eq!(project.next_id, 2);
+
+        changes.undo(1).unwrap();
+        let project = changes.project.as_ref().unwrap();
+        assert_eq!(project.max_per_day, Duration::from_hm(13, 0));
+        assert_eq!(changes.logs(), vec![(1, 8)]);
+    }
+
+    #[test]
+    fn undo_across_the_prune_limit() {
+        let mut changes = Changes::default();
+        changes.change(|_| {});
+        for hours in 0..2 * KEPT_ENTRIES {
+            changes.log(hours % 24 + 1);
+        }
+        assert_eq!(changes.storage.journal.borrow().len(), KEPT_ENTRIES);
+
+        assert!(matches!(
+            changes.undo(KEPT_ENTRIES + 1),
+            Err(Error::InvalidState(_))
+        ));
+        changes.undo(KEPT_ENTRIES).unwrap();
+        assert_eq!(changes.logs().len(), KEPT_ENTRIES);
+        assert_eq!(changes.logs().last().unwrap().0, KEPT_ENTRIES);
+        assert!(matches!(changes.undo(1), Err(Error::InvalidState(_))));
+    }
+
+    #[test]
+    fn history_marks_undone_changes() {
+        let mut changes = Changes::default();
+        changes.change(|_| {});
+        for hours in 1..=3 {
+            changes.log(hours);
+        }
+        changes.undo(2).unwrap();
+        changes.log(4);
+
+        let undone: Vec<bool> = history(&changes.storage, "work", 10)
+            .unwrap()
+            .into_iter()
+            .map(|(_, undone)| undone)
+            .collect();
+        assert_eq!(undone, vec![false, false, true, true, false, false]);
+        assert_eq!(history(&changes.storage, "work", 2).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn renames_are_not_undone() {
+        let mut changes = Changes::default();
+        changes.change(|_| {});
+        record_rename(&changes.storage, "work", "job").unwrap();
+        changes.log(8);
+
+        changes.undo(1).unwrap();
+        assert!(matches!(changes.undo(1), Err(Error::InvalidState(_))));
+        assert!(changes.project.is_some());
+        assert_eq!(
+            active_indices(&read(&changes.storage, "work").unwrap()).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn changes_of_older_versions_are_not_undone() {
+        let mut changes = Changes::default();
+        changes.change(|_| {});
+        changes.storage.journal.borrow_mut().push(
+            r#"{"date":{"year":2024,"month":1,"day":1},"time":{"hours":8,"minutes":0},"command":"log","before":null}"#
+                .to_owned(),
+        );
+        assert!(matches!(changes.undo(1), Err(Error::InvalidState(_))));
+    }
+}
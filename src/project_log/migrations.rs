@@ -0,0 +1,147 @@
+use serde_json::Value;
+
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// Version of the project files written by this version of the program
+pub const CURRENT_VERSION: usize = MIGRATIONS.len();
+
+/// Upgrades a project file one version, where the first one upgrades files from
+/// before they had a version. Never change one which has been released, add a new one instead.
+const MIGRATIONS: &[Migration] = &[drop_period_duration, wrap_overnight_periods];
+
+/// Upgrades a project file to the current version, or explains why it can not be read
+pub fn migrate(project: &mut Value) -> Result<(), String> {
+    let version = match project.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| format!("invalid version {}", version))?
+            as usize,
+    };
+    if version > CURRENT_VERSION {
+        return Err(format!(
+            "saved by a newer version of {} (file version {}, this one reads up to {})",
+            env!("CARGO_PKG_NAME"),
+            version,
+            CURRENT_VERSION
+        ));
+    }
+
+    for migration in &MIGRATIONS[version..] {
+        migration(project)?;
+    }
+    object(project)?.insert("version".to_owned(), CURRENT_VERSION.into());
+    Ok(())
+}
+
+/// Period logs used to save their duration as well, which is always given by the period and breaks
+fn drop_period_duration(project: &mut Value) -> Result<(), String> {
+    for log in period_logs(project)? {
+        object(log)?.remove("duration");
+    }
+    Ok(())
+}
+
+/// Periods ending before they started were saved before they could pass midnight,
+/// making them count negative time
+fn wrap_overnight_periods(project: &mut Value) -> Result<(), String> {
+    for log in period_logs(project)? {
+        let Some(period) = log.get_mut("period") else {
+            continue;
+        };
+        let minutes = |time: Option<&Value>| {
+            let time = time?;
+            Some(time.get("hours")?.as_u64()? * 60 + time.get("minutes")?.as_u64()?)
+        };
+        let from = minutes(period.get("from"));
+        let to = minutes(period.get("to"));
+        let ends_before = matches!((from, to), (Some(from), Some(to)) if to < from);
+        if period.get("days").is_none() && ends_before {
+            object(period)?.insert("days".to_owned(), 1.into());
+        }
+    }
+    Ok(())
+}
+
+fn period_logs(project: &mut Value) -> Result<impl Iterator<Item = &mut Value>, String> {
+    let logs = object(project)?
+        .get_mut("logs")
+        .and_then(Value::as_array_mut)
+        .ok_or("missing logs")?;
+    Ok(logs.iter_mut().filter_map(|log| log.get_mut("Period")))
+}
+
+fn object(value: &mut Value) -> Result<&mut serde_json::Map<String, Value>, String> {
+    value
+        .as_object_mut()
+        .ok_or_else(|| "expected an object".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn period_log(from: (u64, u64), to: (u64, u64)) -> Value {
+        json!({
+            "Period": {
+                "period": {
+                    "from": { "hours": from.0, "minutes": from.1 },
+                    "to": { "hours": to.0, "minutes": to.1 }
+                },
+                "duration": { "minutes": 0 },
+                "breaks": [],
+                "date": { "year": 2022, "month": 11, "day": 7 },
+                "description": "Work"
+            }
+        })
+    }
+
+    #[test]
+    fn drop_period_duration_keeps_duration_logs() {
+        let duration_log = json!({
+            "Duration": {
+                "duration": { "minutes": 90 },
+                "date": { "year": 2022, "month": 11, "day": 8 },
+                "description": null
+            }
+        });
+        let mut project = json!({ "logs": [period_log((8, 0), (16, 0)), duration_log.clone()] });
+
+        drop_period_duration(&mut project).unwrap();
+
+        assert_eq!(project["logs"][0]["Period"].get("duration"), None);
+        assert_eq!(project["logs"][1], duration_log);
+    }
+
+    #[test]
+    fn wrap_overnight_periods_only_wraps_periods_ending_before_they_start() {
+        let mut project = json!({
+            "logs": [period_log((22, 0), (2, 30)), period_log((8, 0), (16, 0))]
+        });
+
+        wrap_overnight_periods(&mut project).unwrap();
+
+        assert_eq!(project["logs"][0]["Period"]["period"]["days"], 1);
+        assert_eq!(project["logs"][1]["Period"]["period"].get("days"), None);
+    }
+
+    #[test]
+    fn migrate_upgrades_files_without_a_version() {
+        let mut project = json!({ "logs": [period_log((23, 0), (7, 0))] });
+
+        migrate(&mut project).unwrap();
+
+        assert_eq!(project["version"], CURRENT_VERSION);
+        let period = &project["logs"][0]["Period"];
+        assert_eq!(period.get("duration"), None);
+        assert_eq!(period["period"]["days"], 1);
+    }
+
+    #[test]
+    fn migrate_refuses_newer_versions() {
+        let mut project = json!({ "version": CURRENT_VERSION + 1, "logs": [] });
+
+        assert!(migrate(&mut project).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::time::{Date, Duration, WEEKDAYS};
+use std::fmt::{Display, Formatter, Result};
+
+/// What length of periods to summarize the logs over
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Grouping {
+    Day,
+    Week,
+    Month,
+}
+
+/// Summary of one period in a report
+pub struct ReportRow {
+    label: String,
+    worked: Duration,
+    expected: Duration,
+    flex: Duration,
+}
+
+impl Grouping {
+    /// Splits the dates from `from` until `to` into consecutive periods,
+    /// where the first and last may be cut short by the range.
+    pub fn periods(&self, from: &Date, to: &Date) -> Vec<(Date, Date)> {
+        let mut periods = vec![];
+        let mut start = from.clone();
+        while &start <= to {
+            let end = self.last_date(&start).min(to.clone());
+            let next = end.add_days(1);
+            periods.push((start, end));
+            start = next;
+        }
+        periods
+    }
+
+    pub fn label(&self, start: &Date) -> String {
+        match self {
+            Grouping::Day => format!("{} {}", start, &WEEKDAYS[start.weekday()][..3]),
+            Grouping::Week => {
+                let (year, week) = start.iso_week();
+                format!("{} week {:02}", year, week)
+            }
+            Grouping::Month => {
+                let (year, month, _) = start.into_ymd();
+                format!("{}-{:02}", year, month)
+            }
+        }
+    }
+
+    fn last_date(&self, date: &Date) -> Date {
+        match self {
+            Grouping::Day => date.clone(),
+            Grouping::Week => date.add_days(6 - date.weekday() as i64),
+            Grouping::Month => date.end_of_month(),
+        }
+    }
+}
+
+impl ReportRow {
+    pub fn new(label: String, worked: Duration, expected: Duration, flex: Duration) -> Self {
+        Self {
+            label,
+            worked,
+            expected,
+            flex,
+        }
+    }
+}
+
+impl Display for ReportRow {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(
+            f,
+            "{:<16} | {:<24} | {:<24} | {}",
+            self.label,
+            self.worked.to_string(),
+            self.expected.to_string(),
+            self.flex
+        )
+    }
+}
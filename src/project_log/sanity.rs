@@ -0,0 +1,221 @@
+use super::{ProjectLog, WorkLog};
+use crate::time::{Date, Duration};
+use std::collections::BTreeMap;
+
+/// Default for the most a project allows to be worked in a day
+pub fn default_max_per_day() -> Duration {
+    Duration::from_hm(13, 0)
+}
+
+/// Everything which looks wrong about adding the logs to the project, such as
+/// periods overlapping each other or those in other projects.
+pub fn problems(project: &ProjectLog, new: &[WorkLog], others: &[ProjectLog]) -> Vec<String> {
+    let mut problems = vec![];
+
+    for (nbr, log) in new.iter().enumerate() {
+        match log.get_period() {
+            Some((period, breaks)) if breaks > period.duration() => problems.push(format!(
+                "The breaks of {} ({}) are longer than the period itself",
+                period, breaks
+            )),
+            _ if log.get_duration() < Duration::from_m(0) => {
+                problems.push(format!("{} has a negative duration", log))
+            }
+            _ => {}
+        }
+
+        for earlier in &new[..nbr] {
+            if overlaps(log, earlier) {
+                problems.push(format!("{} overlaps with {}", log, earlier));
+            }
+        }
+        for existing in project
+            .logs
+            .iter()
+            .filter(|existing| overlaps(log, existing))
+        {
+            problems.push(format!(
+                "{} overlaps with #{} {}",
+                log,
+                existing.get_id(),
+                existing
+            ));
+        }
+        for other in others {
+            for existing in other.logs.iter().filter(|existing| overlaps(log, existing)) {
+                problems.push(format!(
+                    "{} overlaps with #{} {} in project {}",
+                    log,
+                    existing.get_id(),
+                    existing,
+                    other.name
+                ));
+            }
+        }
+    }
+
+    let new_dates: Vec<Date> = new
+        .iter()
+        .filter(|log| !is_multi_day(log))
+        .flat_map(WorkLog::get_duration_per_date)
+        .map(|(date, _)| date)
+        .collect();
+    let mut per_date: BTreeMap<Date, Duration> = BTreeMap::new();
+    for (date, duration) in project
+        .logs
+        .iter()
+        .chain(new)
+        .filter(|log| !is_multi_day(log))
+        .flat_map(WorkLog::get_duration_per_date)
+        .filter(|(date, _)| new_dates.contains(date))
+    {
+        let total = per_date.entry(date).or_insert(Duration::from_m(0));
+        *total = total.clone() + duration;
+    }
+    for (date, total) in per_date {
+        if total > project.max_per_day {
+            problems.push(format!(
+                "{} would be worked on {}, more than the max of {} per day",
+                total, date, project.max_per_day
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Shifts passing more than one midnight, such as being on call, which are not held to
+/// the max per day as they always fill the days in between
+fn is_multi_day(log: &WorkLog) -> bool {
+    log.get_period()
+        .is_some_and(|(period, _)| period.end().1 >= 2)
+}
+
+fn overlaps(log: &WorkLog, other: &WorkLog) -> bool {
+    match (log.get_period(), other.get_period()) {
+        (Some((period, _)), Some((other_period, _))) => {
+            let (date, other_date) = (log.get_date(), other.get_date());
+            let ((start, end), (other_start, other_end)) =
+                match (period.interval(&date), other_period.interval(&other_date)) {
+                    (Some(interval), Some(other_interval)) => (interval, other_interval),
+                    // Periods logged before offsets were kept only tell the time on the clock
+                    _ => (
+                        period.wall_clock_interval(&date),
+                        other_period.wall_clock_interval(&other_date),
+                    ),
+                };
+            start < other_end && other_start < end
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project_log::migrations::CURRENT_VERSION;
+    use crate::project_log::Break;
+    use crate::time::{Period, Time, TimeZone};
+    use serde_json::json;
+
+    fn project(name: &str, logs: Vec<WorkLog>) -> ProjectLog {
+        let mut project: ProjectLog = serde_json::from_value(json!({
+            "version": CURRENT_VERSION,
+            "name": name,
+            "logs": [],
+            "next_id": 1,
+            "start_date": { "year": 2024, "month": 1, "day": 1 },
+        }))
+        .unwrap();
+        project.insert_logs(logs).unwrap();
+        project
+    }
+
+    fn date() -> Date {
+        Date::new(2024, 3, 4)
+    }
+
+    /// A period on the date, in a zone if given or else without offsets
+    fn period(from: usize, to: usize, days: usize, zone: Option<&str>) -> WorkLog {
+        let mut period = Period::spanning(Time::new(from, 0), Time::new(to, 0), days);
+        let zone = zone.map(|zone| zone.parse::<TimeZone>().unwrap());
+        if let Some(zone) = &zone {
+            period = period.localize(&date(), zone);
+        }
+        WorkLog::new_period(period, date(), "Work".to_owned(), vec![], zone)
+    }
+
+    fn hours(hours: i32) -> WorkLog {
+        WorkLog::new_duration(Duration::from_hm(hours, 0), date(), "Work".to_owned())
+    }
+
+    #[test]
+    fn overlaps_within_the_new_logs() {
+        let new = [period(8, 12, 0, None), period(11, 13, 0, None)];
+        let found = problems(&project("work", vec![]), &new, &[]);
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert!(found[0].contains("overlaps with"));
+
+        let new = [period(8, 12, 0, None), period(12, 13, 0, None)];
+        assert!(problems(&project("work", vec![]), &new, &[]).is_empty());
+    }
+
+    #[test]
+    fn overlaps_with_the_project_and_other_projects() {
+        let work = project("work", vec![period(8, 12, 0, None), hours(2)]);
+        let others = [project("other", vec![period(13, 15, 0, None)])];
+        let found = problems(&work, &[period(11, 14, 0, None)], &others);
+        assert_eq!(found.len(), 2, "{:?}", found);
+        assert!(found[0].contains("#1"));
+        assert!(found[1].contains("in project other"));
+    }
+
+    #[test]
+    fn overlaps_in_utc_unless_a_period_has_no_offsets() {
+        // 10-12 at UTC+2 is 8-10 in UTC, the same time as 8-10 at UTC
+        let work = project("work", vec![period(8, 10, 0, Some("UTC"))]);
+        let new = [period(10, 12, 0, Some("UTC+2"))];
+        assert_eq!(problems(&work, &new, &[]).len(), 1);
+
+        // Periods without offsets only tell the time on the clock
+        let new = [period(10, 12, 0, None)];
+        assert!(problems(&work, &new, &[]).is_empty());
+        let new = [period(9, 11, 0, None)];
+        assert_eq!(problems(&work, &new, &[]).len(), 1);
+    }
+
+    #[test]
+    fn breaks_longer_than_the_period() {
+        let pause = Break::Duration(Duration::from_m(90));
+        let log = WorkLog::new_period(
+            Period::new(Time::new(8, 0), Time::new(9, 0)),
+            date(),
+            "Work".to_owned(),
+            vec![pause],
+            None,
+        );
+        let found = problems(&project("work", vec![]), &[log], &[]);
+        assert!(found.iter().any(|problem| problem.contains("breaks")));
+    }
+
+    #[test]
+    fn max_per_day_of_the_project() {
+        let work = project("work", vec![hours(8)]);
+        let others = [project("other", vec![hours(8)])];
+        assert!(problems(&work, &[hours(5)], &others).is_empty());
+
+        let found = problems(&work, &[hours(3), hours(3)], &others);
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert!(found[0].contains("more than the max of 13 hours"));
+    }
+
+    #[test]
+    fn max_per_day_skips_shifts_over_several_days() {
+        let work = project("work", vec![]);
+        assert!(problems(&work, &[period(8, 8, 2, None)], &[]).is_empty());
+        // Past midnight the hours are still counted per date
+        assert_eq!(problems(&work, &[period(8, 8, 1, None)], &[]).len(), 1);
+        assert_eq!(problems(&work, &[period(6, 2, 1, None)], &[]).len(), 1);
+        assert!(problems(&work, &[period(14, 2, 1, None)], &[]).is_empty());
+    }
+}
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use crate::time::{Date, Duration, WEEKDAYS};
+use std::fmt::{Display, Formatter, Result};
+
+/// Expected working hours for each day of the week, starting on monday
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Schedule {
+    hours: [Duration; 7],
+}
+
+impl Schedule {
+    pub fn new(hours: [Duration; 7]) -> Self {
+        Self { hours }
+    }
+
+    pub fn expected(&self, date: &Date) -> Duration {
+        self.hours[date.weekday()].clone()
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        let workday = Duration::from_hm(8, 0);
+        let weekend = Duration::from_m(0);
+        Self::new([
+            workday.clone(),
+            workday.clone(),
+            workday.clone(),
+            workday.clone(),
+            workday,
+            weekend.clone(),
+            weekend,
+        ])
+    }
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        for (day, hours) in WEEKDAYS.iter().zip(&self.hours) {
+            writeln!(f, "{}: {}", &day[..3], hours)?;
+        }
+        let total: Duration = self.hours.iter().cloned().sum();
+        write!(f, "Total: {}", total)
+    }
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Break, WorkLog};
+use crate::error::{Error, Result};
+use crate::time::{now, Date, Period, Time};
+use std::fmt::{self, Display, Formatter};
+
+/// A running clock-in which has not yet been stopped
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Session {
+    date: Date,
+    start: Time,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    breaks: Vec<Period>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    paused: Option<Time>,
+    /// Date the pause began, if not the same as the session started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    paused_on: Option<Date>,
+}
+
+impl Session {
+    pub fn start() -> Self {
+        let (date, start) = now();
+        Self {
+            date,
+            start,
+            breaks: vec![],
+            paused: None,
+            paused_on: None,
+        }
+    }
+
+    /// Starts a break now, returning when it started
+    pub fn pause(&mut self) -> Result<Time> {
+        if let Some(paused) = &self.paused {
+            return Err(Error::InvalidState(format!(
+                "The session is already paused since {}",
+                paused
+            )));
+        }
+
+        let (date, time) = now();
+        self.paused = Some(time.clone());
+        self.paused_on = (date != self.date).then_some(date);
+        Ok(time)
+    }
+
+    /// Ends the current break now, returning the interval it spanned
+    pub fn resume(&mut self) -> Result<Period> {
+        let paused = self
+            .paused
+            .take()
+            .ok_or(Error::InvalidState("The session is not paused".to_owned()))?;
+        let paused_on = self.paused_on.take().unwrap_or(self.date.clone());
+        let (date, time) = now();
+        let days = date.days_since(&paused_on).max(0) as usize;
+        let pause = Period::spanning(paused, time, days);
+        self.breaks.push(pause.clone());
+        Ok(pause)
+    }
+
+    /// Clocks out, turning the session into a period log ending now.
+    ///
+    /// If the session is paused it is considered to have ended when the pause began.
+    pub fn stop(self, description: String, mut breaks: Vec<Break>) -> WorkLog {
+        let (end_date, end) = match self.paused {
+            Some(paused) => (self.paused_on.unwrap_or(self.date.clone()), paused),
+            None => now(),
+        };
+        let days = end_date.days_since(&self.date).max(0) as usize;
+        let period = Period::spanning(self.start, end, days);
+        breaks.extend(self.breaks.into_iter().map(Break::Period));
+        WorkLog::new_period(period, self.date, description, breaks, None)
+    }
+}
+
+impl Display for Session {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.date, self.start)?;
+        if let Some(paused) = &self.paused {
+            write!(f, " (paused since {})", paused)?;
+        }
+        Ok(())
+    }
+}
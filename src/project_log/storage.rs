@@ -0,0 +1,122 @@
+mod json;
+mod ledger;
+mod sqlite;
+
+use super::folders;
+use crate::error::{Error, Result};
+use clap::ValueEnum;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Where the projects and the journals of changes to them are kept.
+///
+/// Projects are given and taken as their serialized JSON, so that the migrations and
+/// the journal work the same for every kind of storage.
+pub trait Storage {
+    fn kind(&self) -> StorageKind;
+
+    /// Names of all projects, sorted
+    fn names(&self) -> Result<Vec<String>>;
+
+    /// The saved project, or None if there is no such project
+    fn load(&self, project: &str) -> Result<Option<String>>;
+
+    fn store(&self, project: &str, content: &str) -> Result<()>;
+
+    /// Removes the project, but keeps its journal so that it can be undone
+    fn remove(&self, project: &str) -> Result<()>;
+
+    /// Moves a project along with its journal to a new name
+    fn rename(&self, project: &str, new_name: &str) -> Result<()>;
+
+    /// All lines of the journal of a project, oldest first
+    fn journal(&self, project: &str) -> Result<Vec<String>>;
+
+    fn append_journal(&self, project: &str, line: &str) -> Result<()>;
+
+    /// Number of lines in the journal of a project
+    fn journal_len(&self, project: &str) -> Result<usize> {
+        Ok(self.journal(project)?.len())
+    }
+
+    /// Drops all but the latest `keep` lines of the journal of a project
+    fn prune_journal(&self, project: &str, keep: usize) -> Result<()>;
+
+    /// Where the project is kept, for error messages
+    fn location(&self, project: &str) -> PathBuf;
+
+    /// Moves all data out of the way after migrating to another storage,
+    /// returning where it was moved.
+    fn retire(&self) -> Result<PathBuf>;
+
+    fn exists(&self, project: &str) -> Result<bool> {
+        Ok(self.load(project)?.is_some())
+    }
+}
+
+/// The kinds of storage, where a SQLite database takes precedence over a ledger if there
+/// is one, and both over JSON files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StorageKind {
+    /// One JSON file per project
+    Json,
+    /// A single SQLite database for all projects
+    Sqlite,
+    /// A plain text file with one line per log, for editing by hand and version control
+    Ledger,
+}
+
+/// Opens the storage in use in the data folder
+pub fn open() -> Result<Box<dyn Storage>> {
+    let folder = folders::log_folder()?;
+    if sqlite::Sqlite::exists_in(&folder) {
+        Ok(Box::new(sqlite::Sqlite::open(&folder)?))
+    } else if ledger::Ledger::exists_in(&folder) {
+        Ok(Box::new(ledger::Ledger::new(folder)))
+    } else {
+        Ok(Box::new(json::Json::new(folder)))
+    }
+}
+
+/// Opens a storage of a kind, creating it if needed
+pub fn create(kind: StorageKind) -> Result<Box<dyn Storage>> {
+    let folder = folders::log_folder()?;
+    match kind {
+        StorageKind::Json => Ok(Box::new(json::Json::new(folder))),
+        StorageKind::Sqlite => {
+            folders::create_folders()?;
+            Ok(Box::new(sqlite::Sqlite::open(&folder)?))
+        }
+        StorageKind::Ledger => Ok(Box::new(ledger::Ledger::new(folder))),
+    }
+}
+
+impl Display for StorageKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            StorageKind::Json => write!(f, "JSON files"),
+            StorageKind::Sqlite => write!(f, "SQLite"),
+            StorageKind::Ledger => write!(f, "a ledger"),
+        }
+    }
+}
+
+/// All lines of a file, or none if there is no such file
+fn read_lines(path: &Path) -> Result<Vec<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content.lines().map(str::to_owned).collect()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(Error::io(path, err)),
+    }
+}
+
+fn append_line(path: &Path, line: &str) -> Result<()> {
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", line))
+        .map_err(|err| Error::io(path, err))
+}
@@ -0,0 +1,118 @@
+use super::{append_line, read_lines, Storage, StorageKind};
+use crate::error::{Error, Result};
+use crate::project_log::{files, folders};
+use std::fs;
+use std::path::PathBuf;
+
+const FILE_TYPE: &str = "json";
+const JOURNAL_TYPE: &str = "journal";
+/// The project before it was last saved, kept by `files::write_atomic`
+const BACKUP_TYPE: &str = "json.bak";
+const RETIRED_FOLDER: &str = "json-backup";
+
+/// Each project in its own JSON file, with the journal in a file next to it
+pub struct Json {
+    folder: PathBuf,
+}
+
+impl Json {
+    pub fn new(folder: PathBuf) -> Self {
+        Self { folder }
+    }
+
+    fn path(&self, project: &str, file_type: &str) -> PathBuf {
+        self.folder.join(format!("{}.{}", project, file_type))
+    }
+}
+
+impl Storage for Json {
+    fn kind(&self) -> StorageKind {
+        StorageKind::Json
+    }
+
+    fn names(&self) -> Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.folder) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(Error::io(&self.folder, err)),
+        };
+
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == FILE_TYPE))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn load(&self, project: &str) -> Result<Option<String>> {
+        let path = self.path(project, FILE_TYPE);
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(Some(content)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::io(&path, err)),
+        }
+    }
+
+    fn store(&self, project: &str, content: &str) -> Result<()> {
+        folders::create_folders()?;
+        files::write_atomic(&self.path(project, FILE_TYPE), content)
+    }
+
+    fn remove(&self, project: &str) -> Result<()> {
+        let path = self.path(project, FILE_TYPE);
+        fs::remove_file(&path).map_err(|err| Error::io(&path, err))
+    }
+
+    fn rename(&self, project: &str, new_name: &str) -> Result<()> {
+        for file_type in [JOURNAL_TYPE, BACKUP_TYPE] {
+            let path = self.path(project, file_type);
+            match fs::rename(&path, self.path(new_name, file_type)) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(Error::io(&path, err))
+                }
+                _ => {}
+            }
+        }
+
+        let path = self.path(project, FILE_TYPE);
+        fs::rename(&path, self.path(new_name, FILE_TYPE)).map_err(|err| Error::io(&path, err))
+    }
+
+    fn journal(&self, project: &str) -> Result<Vec<String>> {
+        read_lines(&self.path(project, JOURNAL_TYPE))
+    }
+
+    fn append_journal(&self, project: &str, line: &str) -> Result<()> {
+        folders::create_folders()?;
+        append_line(&self.path(project, JOURNAL_TYPE), line)
+    }
+
+    fn prune_journal(&self, project: &str, keep: usize) -> Result<()> {
+        let path = self.path(project, JOURNAL_TYPE);
+        let lines = read_lines(&path)?;
+        let kept = &lines[lines.len().saturating_sub(keep)..];
+        files::write_atomic(&path, &(kept.join("\n") + "\n"))
+    }
+
+    fn location(&self, project: &str) -> PathBuf {
+        self.path(project, FILE_TYPE)
+    }
+
+    fn retire(&self) -> Result<PathBuf> {
+        let backup = self.folder.join(RETIRED_FOLDER);
+        fs::create_dir_all(&backup).map_err(|err| Error::io(&backup, err))?;
+
+        for project in self.names()? {
+            for file_type in [FILE_TYPE, JOURNAL_TYPE, BACKUP_TYPE] {
+                let path = self.path(project.as_str(), file_type);
+                if path.exists() {
+                    let target = backup.join(path.file_name().expect("Paths to files have names"));
+                    fs::rename(&path, &target).map_err(|err| Error::io(&path, err))?;
+                }
+            }
+        }
+        Ok(backup)
+    }
+}
@@ -0,0 +1,943 @@
+use super::{append_line, read_lines, Storage, StorageKind};
+use crate::error::{Error, Result};
+use crate::parser::{parse_duration, parse_iso_date, parse_period};
+use crate::project_log::files::{self, ProjectLock};
+use crate::project_log::Absence;
+use crate::time::{Date, Duration, Period, TimeZone};
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FILE_NAME: &str = "flex-cli.ledger";
+const JOURNAL_FILE_NAME: &str = "flex-cli.ledger.journal";
+const LOCK_FILE_NAME: &str = "flex-cli.ledger.lock";
+const RETIRED_FOLDER: &str = "ledger-backup";
+
+const HEADER: &str = "\
+# Logs of flex-cli, one per line as
+#   <date> <from-to[+days]|hours:minutes|absence [half]> [-<break>]... <project> [\"description\"] [#id]
+# such as
+#   2023-04-03 08:15-16:40 -0:30 work \"Fixed the build\"
+# Lines starting with `project` hold the settings of each project, and lines starting with # are ignored.
+";
+
+/// All projects in one plain text file with a line per log, which can be edited by hand.
+/// Lines are only rewritten when their log changes, and new logs are added at the end,
+/// so that the file is easy to follow in version control.
+pub struct Ledger {
+    folder: PathBuf,
+}
+
+/// A line of the ledger, where the text is kept as written until its log changes
+enum Line {
+    Other(String),
+    Project {
+        name: String,
+        settings: Value,
+        text: Option<String>,
+    },
+    Log {
+        project: String,
+        log: Value,
+        text: Option<String>,
+    },
+}
+
+impl Ledger {
+    pub fn exists_in(folder: &Path) -> bool {
+        folder.join(FILE_NAME).exists()
+    }
+
+    pub fn new(folder: PathBuf) -> Self {
+        Self { folder }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.folder.join(FILE_NAME)
+    }
+
+    fn journal_path(&self) -> PathBuf {
+        self.folder.join(JOURNAL_FILE_NAME)
+    }
+
+    fn create_folder(&self) -> Result<()> {
+        fs::create_dir_all(&self.folder).map_err(|err| Error::io(&self.folder, err))
+    }
+
+    fn read(&self) -> Result<Vec<Line>> {
+        let path = self.path();
+        let lines = read_lines(&path)?;
+        if lines.is_empty() {
+            return Ok(HEADER
+                .lines()
+                .map(|line| Line::Other(line.to_owned()))
+                .collect());
+        }
+
+        let mut lines = lines
+            .into_iter()
+            .enumerate()
+            .map(|(nbr, line)| {
+                parse_line(&line).map_err(|reason| Error::Corrupt {
+                    path: path.clone(),
+                    reason: format!("line {}: {}", nbr + 1, reason),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let projects: BTreeSet<&str> = lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Project { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        for (nbr, line) in lines.iter().enumerate() {
+            let Line::Log { project, .. } = line else {
+                continue;
+            };
+            if !projects.contains(project.as_str()) {
+                return Err(Error::Corrupt {
+                    path,
+                    reason: format!(
+                        "line {}: there is no project {}, add a `project` line for it",
+                        nbr + 1,
+                        quote_project(project)
+                    ),
+                });
+            }
+        }
+        assign_missing_ids(&mut lines);
+        Ok(lines)
+    }
+
+    /// Changes the ledger, without anyone else changing it at the same time
+    fn update(&self, change: impl FnOnce(&mut Vec<Line>) -> Result<()>) -> Result<()> {
+        self.create_folder()?;
+        let _lock = ProjectLock::at(&self.folder.join(LOCK_FILE_NAME))?;
+        let mut lines = self.read()?;
+        change(&mut lines)?;
+
+        let content = lines
+            .iter()
+            .map(|line| line.text())
+            .collect::<std::result::Result<Vec<String>, String>>()
+            .map_err(|reason| Error::Corrupt {
+                path: self.path(),
+                reason,
+            })?;
+        files::write_atomic(&self.path(), &(content.join("\n") + "\n"))
+    }
+
+    /// The journal entries along with the project of each
+    fn journal_entries(&self) -> Result<Vec<(String, String)>> {
+        let path = self.journal_path();
+        read_lines(&path)?
+            .iter()
+            .map(|line| {
+                serde_json::from_str(line).map_err(|err| Error::Corrupt {
+                    path: path.clone(),
+                    reason: err.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Storage for Ledger {
+    fn kind(&self) -> StorageKind {
+        StorageKind::Ledger
+    }
+
+    fn names(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = self
+            .read()?
+            .into_iter()
+            .filter_map(|line| match line {
+                Line::Project { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn load(&self, project: &str) -> Result<Option<String>> {
+        let lines = self.read()?;
+        let mut settings = None;
+        let mut logs = vec![];
+        for line in lines {
+            match line {
+                Line::Project {
+                    name,
+                    settings: found,
+                    ..
+                } if name == project => settings = Some(found),
+                Line::Log {
+                    project: of, log, ..
+                } if of == project => logs.push(log),
+                _ => {}
+            }
+        }
+        let Some(Value::Object(mut settings)) = settings else {
+            return Ok(None);
+        };
+
+        let next_id =
+            next_free_id(&logs).max(settings.get("next_id").and_then(Value::as_u64).unwrap_or(0));
+        settings.insert("next_id".to_owned(), next_id.into());
+        settings.insert("name".to_owned(), project.into());
+        settings.insert("logs".to_owned(), Value::Array(logs));
+        Ok(Some(Value::Object(settings).to_string()))
+    }
+
+    fn store(&self, project: &str, content: &str) -> Result<()> {
+        let corrupt = |reason: String| Error::Corrupt {
+            path: self.path(),
+            reason,
+        };
+        let mut settings: Value =
+            serde_json::from_str(content).map_err(|err| corrupt(err.to_string()))?;
+        let object = settings
+            .as_object_mut()
+            .ok_or_else(|| corrupt("expected an object".to_owned()))?;
+        object.remove("name");
+        let mut new_logs = match object.remove("logs") {
+            Some(Value::Array(logs)) => logs,
+            _ => return Err(corrupt("missing logs".to_owned())),
+        };
+        // The next id is only kept when the latest logs were removed, so that their ids
+        // are not given out again, to not change the line of the project with every log
+        let next_id = object.remove("next_id").and_then(|id| id.as_u64());
+        if let Some(next_id) = next_id.filter(|id| *id > next_free_id(&new_logs)) {
+            object.insert("next_id".to_owned(), next_id.into());
+        }
+
+        self.update(|lines| {
+            let mut has_project = false;
+            lines.retain_mut(|line| match line {
+                Line::Project {
+                    name,
+                    settings: old,
+                    text,
+                } if name == project => {
+                    has_project = true;
+                    if *old != settings {
+                        *old = settings.clone();
+                        *text = None;
+                    }
+                    true
+                }
+                Line::Log {
+                    project: of,
+                    log,
+                    text,
+                } if of == project => {
+                    let id = log_id(log);
+                    let Some(index) = new_logs.iter().position(|new| log_id(new) == id) else {
+                        return false;
+                    };
+                    let new = new_logs.remove(index);
+                    // Compared as written, as saving may fill in fields left out of the line
+                    if format_log(project, log) != format_log(project, &new) {
+                        *log = new;
+                        *text = None;
+                    }
+                    true
+                }
+                _ => true,
+            });
+
+            if !has_project {
+                lines.push(Line::Other(String::new()));
+                lines.push(Line::Project {
+                    name: project.to_owned(),
+                    settings: settings.clone(),
+                    text: None,
+                });
+            }
+            lines.extend(new_logs.drain(..).map(|log| Line::Log {
+                project: project.to_owned(),
+                log,
+                text: None,
+            }));
+            Ok(())
+        })
+    }
+
+    fn remove(&self, project: &str) -> Result<()> {
+        self.update(|lines| {
+            lines.retain(|line| line.project() != Some(project));
+            Ok(())
+        })
+    }
+
+    fn rename(&self, project: &str, new_name: &str) -> Result<()> {
+        self.update(|lines| {
+            for line in lines.iter_mut() {
+                match line {
+                    Line::Project { name, text, .. } if name == project => {
+                        *name = new_name.to_owned();
+                        *text = None;
+                    }
+                    Line::Log {
+                        project: of, text, ..
+                    } if of == project => {
+                        *of = new_name.to_owned();
+                        *text = None;
+                    }
+                    _ => {}
+                }
+            }
+            Ok(())
+        })?;
+
+        let entries = self
+            .journal_entries()?
+            .into_iter()
+            .map(|(of, entry)| {
+                let of = if of == project {
+                    new_name.to_owned()
+                } else {
+                    of
+                };
+                serde_json::to_string(&(of, entry)).expect("Failed to serialize journal entry.")
+            })
+            .collect::<Vec<_>>();
+        if entries.is_empty() {
+            return Ok(());
+        }
+        files::write_atomic(&self.journal_path(), &(entries.join("\n") + "\n"))
+    }
+
+    fn journal(&self, project: &str) -> Result<Vec<String>> {
+        Ok(self
+            .journal_entries()?
+            .into_iter()
+            .filter(|(of, _)| of == project)
+            .map(|(_, entry)| entry)
+            .collect())
+    }
+
+    fn append_journal(&self, project: &str, line: &str) -> Result<()> {
+        self.create_folder()?;
+        let line =
+            serde_json::to_string(&(project, line)).expect("Failed to serialize journal entry.");
+        append_line(&self.journal_path(), &line)
+    }
+
+    fn prune_journal(&self, project: &str, keep: usize) -> Result<()> {
+        let entries = self.journal_entries()?;
+        let mut dropped = entries
+            .iter()
+            .filter(|(of, _)| of == project)
+            .count()
+            .saturating_sub(keep);
+        let kept = entries
+            .into_iter()
+            .filter(|(of, _)| {
+                let drop = of == project && dropped > 0;
+                dropped -= drop as usize;
+                !drop
+            })
+            .map(|entry| serde_json::to_string(&entry).expect("Failed to serialize journal entry."))
+            .collect::<Vec<_>>();
+        files::write_atomic(&self.journal_path(), &(kept.join("\n") + "\n"))
+    }
+
+    fn location(&self, _project: &str) -> PathBuf {
+        self.path()
+    }
+
+    fn retire(&self) -> Result<PathBuf> {
+        let backup = self.folder.join(RETIRED_FOLDER);
+        fs::create_dir_all(&backup).map_err(|err| Error::io(&backup, err))?;
+
+        for path in [
+            self.path(),
+            files::with_suffix(&self.path(), ".bak"),
+            self.journal_path(),
+        ] {
+            if path.exists() {
+                let target = backup.join(path.file_name().expect("Paths to files have names"));
+                fs::rename(&path, &target).map_err(|err| Error::io(&path, err))?;
+            }
+        }
+        Ok(backup)
+    }
+}
+
+impl Line {
+    fn project(&self) -> Option<&str> {
+        match self {
+            Line::Other(_) => None,
+            Line::Project { name, .. } => Some(name),
+            Line::Log { project, .. } => Some(project),
+        }
+    }
+
+    /// The line as written, or formatted anew if it has changed
+    fn text(&self) -> std::result::Result<String, String> {
+        match self {
+            Line::Other(text)
+            | Line::Project {
+                text: Some(text), ..
+            }
+            | Line::Log {
+                text: Some(text), ..
+            } => Ok(text.clone()),
+            Line::Project { name, settings, .. } => {
+                Ok(format!("project {} {}", quote_project(name), settings))
+            }
+            Line::Log { project, log, .. } => format_log(project, log),
+        }
+    }
+}
+
+/// Gives logs added by hand without an id the next free ids of their project, in the
+/// order of the file, so that they get the same ids every time until saved with them.
+fn assign_missing_ids(lines: &mut [Line]) {
+    let mut next_ids: BTreeMap<String, u64> = BTreeMap::new();
+    for line in lines.iter() {
+        let (project, next) = match line {
+            Line::Project { name, settings, .. } => {
+                (name, settings.get("next_id").and_then(Value::as_u64))
+            }
+            Line::Log { project, log, .. } => (project, log_id(log).map(|id| id + 1)),
+            Line::Other(_) => continue,
+        };
+        let next_id = next_ids.entry(project.clone()).or_insert(1);
+        *next_id = (*next_id).max(next.unwrap_or(0));
+    }
+
+    for line in lines.iter_mut() {
+        if let Line::Log { project, log, .. } = line {
+            if log_id(log).is_none() {
+                let next_id = next_ids.entry(project.clone()).or_insert(1);
+                if let Some(fields) = log_fields(log) {
+                    fields.insert("id".to_owned(), (*next_id).into());
+                }
+                *next_id += 1;
+            }
+        }
+    }
+}
+
+/// The id after the highest one of the logs
+fn next_free_id(logs: &[Value]) -> u64 {
+    logs.iter().filter_map(log_id).max().unwrap_or(0) + 1
+}
+
+/// The id of a log, or None if it has not been given one yet
+fn log_id(log: &Value) -> Option<u64> {
+    let (_, fields) = log.as_object()?.iter().next()?;
+    fields.get("id")?.as_u64().filter(|id| *id > 0)
+}
+
+/// The fields of a log, which is saved as its kind wrapping them
+fn log_fields(log: &mut Value) -> Option<&mut Map<String, Value>> {
+    log.as_object_mut()?.values_mut().next()?.as_object_mut()
+}
+
+fn parse_line(line: &str) -> std::result::Result<Line, String> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return Ok(Line::Other(line.to_owned()));
+    }
+
+    let mut tokens = tokenize(trimmed)?.into_iter().peekable();
+    if tokens.peek() == Some(&Token::Word("project".to_owned())) {
+        tokens.next();
+        let name = tokens.next().ok_or("missing project name")?.text();
+        let settings = tokens.next().ok_or("missing project settings")?.text();
+        let settings: Value = serde_json::from_str(&settings).map_err(|err| err.to_string())?;
+        return Ok(Line::Project {
+            name,
+            settings,
+            text: Some(line.to_owned()),
+        });
+    }
+
+    let (project, log, has_id) = parse_log(tokens)?;
+    Ok(Line::Log {
+        project,
+        log,
+        text: has_id.then(|| line.to_owned()),
+    })
+}
+
+/// Parses a log, telling if it had an id
+fn parse_log(
+    mut tokens: std::iter::Peekable<std::vec::IntoIter<Token>>,
+) -> std::result::Result<(String, Value, bool), String> {
+    let mut word = |what: &str| match tokens.next() {
+        Some(Token::Word(word)) => Ok(word),
+        _ => Err(format!("missing {}", what)),
+    };
+    let date = parse_iso_date(&word("date")?).map_err(|err| err.to_string())?;
+    let what = word("period, duration or absence")?;
+
+    let mut fields = Map::new();
+    fields.insert("date".to_owned(), to_value(&date));
+    let kind = if is_period(&what) {
+        let period = parse_period(&what).map_err(|err| err.to_string())?;
+        let mut breaks = vec![];
+        while let Some(Token::Word(word)) = tokens.peek() {
+            let Some(pause) = word.strip_prefix('-') else {
+                break;
+            };
+            let pause = if is_period(pause) {
+                parse_period(pause).map(|pause| to_value(&pause))
+            } else {
+                parse_duration(pause).map(|pause| to_value(&pause))
+            };
+            breaks.push(pause.map_err(|err| format!("invalid break: {}", err))?);
+            tokens.next();
+        }
+        fields.insert("period".to_owned(), to_value(&period));
+        fields.insert("breaks".to_owned(), Value::Array(breaks));
+        "Period"
+    } else if let Some(duration) = parse_signed_duration(&what) {
+        fields.insert("duration".to_owned(), to_value(&duration));
+        "Duration"
+    } else if let Ok(absence) = Absence::from_str(&what, true) {
+        let half_day = tokens.next_if_eq(&Token::Word("half".to_owned())).is_some();
+        fields.insert("kind".to_owned(), to_value(&absence));
+        fields.insert("half_day".to_owned(), half_day.into());
+        "Absence"
+    } else {
+        return Err(format!(
+            "expected a period, duration or absence, got '{}'",
+            what
+        ));
+    };
+
+    let project = tokens.next().ok_or("missing project")?.text();
+    if let Some(Token::Quoted(description)) =
+        tokens.next_if(|token| matches!(token, Token::Quoted(_)))
+    {
+        fields.insert("description".to_owned(), description.into());
+    }
+
+    let mut has_id = false;
+    for token in tokens {
+        let word = match token {
+            Token::Word(word) => word,
+            Token::Quoted(text) => return Err(format!("unexpected \"{}\"", text)),
+        };
+        if let Some(id) = word.strip_prefix('#') {
+            let id: u64 = id.parse().map_err(|_| format!("invalid id '{}'", word))?;
+            fields.insert("id".to_owned(), id.into());
+            has_id = id > 0;
+        } else if let Some(zone) = word.strip_prefix("tz=") {
+            zone.parse::<TimeZone>()?;
+            fields.insert("timezone".to_owned(), zone.into());
+        } else if let Some(offsets) = word.strip_prefix("utc=") {
+            let offsets =
+                parse_offsets(offsets).ok_or_else(|| format!("invalid offsets '{}'", word))?;
+            let period = fields
+                .get_mut("period")
+                .and_then(Value::as_object_mut)
+                .ok_or("only periods have offsets")?;
+            period.insert("offsets".to_owned(), offsets);
+        } else {
+            return Err(format!("unexpected '{}'", word));
+        }
+    }
+
+    let mut log = Map::new();
+    log.insert(kind.to_owned(), Value::Object(fields));
+    Ok((project, Value::Object(log), has_id))
+}
+
+/// Formats a log as saved by the project, leaving out the offsets of breaks as they
+/// only matter for breaks over a daylight saving change
+fn format_log(project: &str, log: &Value) -> std::result::Result<String, String> {
+    let invalid = || format!("can not write log {}", log);
+    let (kind, fields) = log
+        .as_object()
+        .and_then(|kinds| kinds.iter().next())
+        .ok_or_else(invalid)?;
+    let field = |name: &str| fields.get(name).ok_or_else(invalid);
+
+    let date: Date = from_value(field("date")?).ok_or_else(invalid)?;
+    let mut parts = vec![date.to_string()];
+    match kind.as_str() {
+        "Period" => {
+            let period: Period = from_value(field("period")?).ok_or_else(invalid)?;
+            parts.push(period.to_string());
+            for pause in field("breaks")?.as_array().ok_or_else(invalid)? {
+                let pause = match from_value::<Period>(pause) {
+                    Some(period) => period.to_string(),
+                    None => format_duration(&from_value(pause).ok_or_else(invalid)?),
+                };
+                parts.push(format!("-{}", pause));
+            }
+        }
+        "Duration" => {
+            parts.push(format_duration(
+                &from_value(field("duration")?).ok_or_else(invalid)?,
+            ));
+        }
+        "Absence" => {
+            let absence: Absence = from_value(field("kind")?).ok_or_else(invalid)?;
+            let name = absence.to_possible_value().ok_or_else(invalid)?;
+            parts.push(name.get_name().to_owned());
+            if fields.get("half_day") == Some(&Value::Bool(true)) {
+                parts.push("half".to_owned());
+            }
+        }
+        _ => return Err(invalid()),
+    }
+
+    parts.push(quote_project(project));
+    if let Some(description) = fields.get("description").and_then(Value::as_str) {
+        parts.push(quote(description));
+    }
+    parts.push(format!("#{}", log_id(log).ok_or_else(invalid)?));
+    if let Some(zone) = fields.get("timezone").and_then(Value::as_str) {
+        parts.push(format!("tz={}", zone));
+    }
+    if let Some(offsets) = field("period")
+        .ok()
+        .and_then(|period| period.get("offsets"))
+    {
+        let offsets: (i32, i32) = from_value(offsets).ok_or_else(invalid)?;
+        parts.push(format!("utc={}", format_offsets(offsets)));
+    }
+    Ok(parts.join(" "))
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+}
+
+impl Token {
+    fn text(self) -> String {
+        match self {
+            Token::Word(text) | Token::Quoted(text) => text,
+        }
+    }
+}
+
+/// Splits a line on whitespace, except within quotes where `\"` and `\\` are escapes
+fn tokenize(line: &str) -> std::result::Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next().ok_or("missing closing quote")? {
+                    '"' => break,
+                    '\\' => match chars.next().ok_or("missing closing quote")? {
+                        'n' => text.push('\n'),
+                        escaped => text.push(escaped),
+                    },
+                    c => text.push(c),
+                }
+            }
+            tokens.push(Token::Quoted(text));
+        } else {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+            // Settings of projects are JSON, which may contain spaces within strings
+            if word.starts_with('{') {
+                word.extend(chars.by_ref());
+                word = word.trim_end().to_owned();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+    Ok(tokens)
+}
+
+fn quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Quotes names which would otherwise be read as something else, along with any
+/// looking like another part of a line such as a date, period, duration or absence
+fn quote_project(name: &str) -> String {
+    let reserved = ["project", "half"].contains(&name)
+        || Absence::from_str(name, true).is_ok()
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || name.contains('=');
+    let plain = !name.is_empty()
+        && !reserved
+        && !name.starts_with(['-', '#', '{'])
+        && !name.contains(|c: char| c.is_whitespace() || c == '"');
+    if plain {
+        name.to_owned()
+    } else {
+        quote(name)
+    }
+}
+
+/// Tells if a word is meant as a period rather than a duration or an absence
+fn is_period(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_digit()) && word.contains('-')
+}
+
+/// Parses `[-]hours[:minutes]`, as durations are only kept from being negative
+/// unless forced
+fn parse_signed_duration(duration_str: &str) -> Option<Duration> {
+    match duration_str.strip_prefix('-') {
+        Some(duration_str) => parse_duration(duration_str)
+            .ok()
+            .map(|duration| Duration::from_m(-duration.to_m())),
+        None => parse_duration(duration_str).ok(),
+    }
+}
+
+fn format_duration(duration: &Duration) -> String {
+    let minutes = duration.to_m();
+    let sign = if minutes < 0 { "-" } else { "" };
+    format!("{}{}:{:02}", sign, minutes.abs() / 60, minutes.abs() % 60)
+}
+
+/// Parses the UTC offsets at the start and end of a period as `+02:00[/+01:00]`
+fn parse_offsets(offsets_str: &str) -> Option<Value> {
+    let offset = |offset_str: &str| {
+        let (sign, rest) = match offset_str.split_at_checked(1)? {
+            ("+", rest) => (1, rest),
+            ("-", rest) => (-1, rest),
+            _ => return None,
+        };
+        let (hours, minutes) = rest.split_once(':')?;
+        let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+        ((0..=24).contains(&hours) && (0..60).contains(&minutes))
+            .then(|| sign * (hours * 60 + minutes))
+    };
+    let (from, to) = match offsets_str.split_once('/') {
+        Some((from, to)) => (offset(from)?, offset(to)?),
+        None => (offset(offsets_str)?, offset(offsets_str)?),
+    };
+    Some(to_value(&(from, to)))
+}
+
+fn format_offsets((from, to): (i32, i32)) -> String {
+    let offset = |offset: i32| {
+        let sign = if offset < 0 { '-' } else { '+' };
+        format!("{}{:02}:{:02}", sign, offset.abs() / 60, offset.abs() % 60)
+    };
+    if from == to {
+        offset(from)
+    } else {
+        format!("{}/{}", offset(from), offset(to))
+    }
+}
+
+fn to_value<T: serde::Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).expect("Failed to serialize log.")
+}
+
+fn from_value<T: serde::de::DeserializeOwned>(value: &Value) -> Option<T> {
+    serde_json::from_value(value.clone()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project_log::{Break, WorkLog};
+    use crate::time::Time;
+
+    /// An empty folder of its own for each test
+    fn test_folder(name: &str) -> PathBuf {
+        let folder =
+            std::env::temp_dir().join(format!("flex-cli-ledger-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    fn logs() -> Vec<WorkLog> {
+        let date = Date::new(2024, 3, 30);
+        let zone: TimeZone = "Europe/Stockholm".parse().unwrap();
+        let night = Period::new(Time::new(22, 0), Time::new(6, 0)).localize(&date, &zone);
+        let pause = Period::new(Time::new(1, 0), Time::new(1, 30));
+        let mut logs = vec![
+            WorkLog::new_period(
+                night,
+                date.clone(),
+                "Night \"shift\"\nwith a \\ and a newline".to_owned(),
+                vec![Break::Duration(Duration::from_m(20)), Break::Period(pause)],
+                Some(zone),
+            ),
+            WorkLog::new_duration(Duration::from_hm(-1, -30), date.clone(), "Fix".to_owned()),
+            WorkLog::new_absence(Absence::Vacation, date.clone(), true, None),
+            WorkLog::new_absence(Absence::Sick, date, false, Some("Flu".to_owned())),
+        ];
+        for (index, log) in logs.iter_mut().enumerate() {
+            log.set_id(index + 1);
+        }
+        logs
+    }
+
+    #[test]
+    fn logs_read_back_as_written() {
+        for project in [
+            "work",
+            "my project",
+            "half",
+            "vacation",
+            "2024",
+            "-x",
+            "a=b",
+            "#1",
+        ] {
+            for log in logs() {
+                let mut value = to_value(&log);
+                let line = format_log(project, &value).unwrap();
+                // The offsets of breaks are left out on purpose
+                if let Some(Value::Array(breaks)) =
+                    log_fields(&mut value).unwrap().get_mut("breaks")
+                {
+                    for pause in breaks.iter_mut().filter_map(Value::as_object_mut) {
+                        pause.remove("offsets");
+                    }
+                }
+                let Ok(Line::Log {
+                    project: read_project,
+                    log: read_log,
+                    text,
+                }) = parse_line(&line)
+                else {
+                    panic!("{} was not read as a log", line);
+                };
+
+                assert_eq!(read_project, project, "{}", line);
+                assert_eq!(text, Some(line.clone()));
+                let read_log: WorkLog = from_value(&read_log).expect("a log");
+                assert_eq!(to_value(&read_log), value, "{}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn project_names_are_quoted_when_reserved() {
+        assert_eq!(quote_project("work"), "work");
+        for name in [
+            "half",
+            "Vacation",
+            "comp",
+            "2024-06-08",
+            "8:00",
+            "project",
+            "tz=UTC",
+        ] {
+            assert_eq!(quote_project(name), quote(name));
+        }
+    }
+
+    #[test]
+    fn written_by_hand() {
+        let folder = test_folder("by-hand");
+        let settings = r#"{"start_date":{"year":2024,"month":6,"day":1},"version":2}"#;
+        let content = format!(
+            "{}\nproject \"my project\" {}\n\
+             2024-06-03 8-12 -0:15 \"my project\" \"Planning\" #4\n\
+             2024-06-04 8:00 \"my project\"\n\
+             # 2024-06-05 8:00 \"my project\"\n\
+             2024-06-06 vacation half \"my project\"\n",
+            HEADER, settings
+        );
+        fs::write(folder.join(FILE_NAME), content).unwrap();
+        let ledger = Ledger::new(folder.clone());
+
+        assert_eq!(ledger.names().unwrap(), vec!["my project".to_owned()]);
+        assert_eq!(ledger.load("work").unwrap(), None);
+        let project: Value =
+            serde_json::from_str(&ledger.load("my project").unwrap().unwrap()).unwrap();
+        let logs: Vec<WorkLog> = from_value(&project["logs"]).unwrap();
+        let ids: Vec<usize> = logs.iter().map(WorkLog::get_id).collect();
+        assert_eq!(ids, vec![4, 5, 6]);
+        assert_eq!(project["next_id"], 7);
+        assert_eq!(logs[0].get_duration(), Duration::from_hm(3, 45));
+        assert_eq!(logs[1].get_duration(), Duration::from_hm(8, 0));
+        assert_eq!(logs[2].get_absence(), Some((Absence::Vacation, true)));
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn ids_of_removed_logs_are_not_given_again() {
+        let folder = test_folder("removed-ids");
+        let ledger = Ledger::new(folder.clone());
+        let project = |logs: &[WorkLog], next_id: usize| {
+            format!(
+                r#"{{"name":"work","start_date":{{"year":2024,"month":3,"day":1}},"version":2,"next_id":{},"logs":{}}}"#,
+                next_id,
+                to_value(&logs)
+            )
+        };
+        let next_id = |ledger: &Ledger| {
+            let project: Value =
+                serde_json::from_str(&ledger.load("work").unwrap().unwrap()).unwrap();
+            project["next_id"].as_u64().unwrap()
+        };
+        let mut logs = logs();
+        ledger.store("work", &project(&logs, 5)).unwrap();
+        assert_eq!(next_id(&ledger), 5);
+
+        // Removing the newest log keeps its id taken
+        logs.pop();
+        ledger.store("work", &project(&logs, 5)).unwrap();
+        assert_eq!(next_id(&ledger), 5);
+        let content = fs::read_to_string(folder.join(FILE_NAME)).unwrap();
+        assert!(content.contains(r#""next_id":5"#), "{}", content);
+
+        // As it is for logs added by hand
+        fs::write(folder.join(FILE_NAME), content + "2024-03-31 2:00 work\n").unwrap();
+        let project_value: Value =
+            serde_json::from_str(&ledger.load("work").unwrap().unwrap()).unwrap();
+        let read: Vec<WorkLog> = from_value(&project_value["logs"]).unwrap();
+        assert_eq!(read.last().unwrap().get_id(), 5);
+        assert_eq!(next_id(&ledger), 6);
+
+        // Until a log is given the id, after which the project line no longer needs it
+        let mut log = WorkLog::new_duration(
+            Duration::from_hm(2, 0),
+            Date::new(2024, 3, 31),
+            "Work".to_owned(),
+        );
+        log.set_id(5);
+        logs.push(log);
+        ledger.store("work", &project(&logs, 6)).unwrap();
+        assert_eq!(next_id(&ledger), 6);
+        let content = fs::read_to_string(folder.join(FILE_NAME)).unwrap();
+        assert!(!content.contains("next_id"), "{}", content);
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn logs_of_unknown_projects_are_refused() {
+        let folder = test_folder("unknown-project");
+        fs::write(
+            folder.join(FILE_NAME),
+            "project work {}\n2024-06-08 8:00 half \"desc\"\n",
+        )
+        .unwrap();
+
+        match Ledger::new(folder.clone()).load("work") {
+            Err(Error::Corrupt { reason, .. }) => assert!(reason.starts_with("line 2:")),
+            other => panic!("expected the line to be refused, got {:?}", other),
+        }
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
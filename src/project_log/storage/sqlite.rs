@@ -0,0 +1,430 @@
+use super::{Storage, StorageKind};
+use crate::error::{Error, Result};
+use crate::project_log::files;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FILE_NAME: &str = "flex-cli.sqlite";
+const RETIRED_FILE_NAME: &str = "flex-cli.sqlite.bak";
+
+/// How long to wait for another command writing to the database
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS projects (
+        name TEXT PRIMARY KEY,
+        settings TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS logs (
+        project TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        date TEXT NOT NULL,
+        log TEXT NOT NULL,
+        PRIMARY KEY (project, id)
+    );
+    CREATE INDEX IF NOT EXISTS logs_by_date ON logs (project, date);
+    CREATE TABLE IF NOT EXISTS journal (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        project TEXT NOT NULL,
+        entry TEXT NOT NULL
+    );
+";
+
+/// Id, date and the serialized log
+type LogRow = (i64, String, String);
+
+/// All projects in one SQLite database, with each log in its own row so that saving
+/// a project only writes the logs which changed.
+pub struct Sqlite {
+    path: PathBuf,
+    conn: Connection,
+}
+
+impl Sqlite {
+    pub fn exists_in(folder: &Path) -> bool {
+        folder.join(FILE_NAME).exists()
+    }
+
+    /// Opens the database in a folder, creating it if there is none
+    pub fn open(folder: &Path) -> Result<Self> {
+        let path = folder.join(FILE_NAME);
+        let conn = Connection::open(&path).map_err(|err| Error::database(&path, err))?;
+        Self::with_connection(path, conn)
+    }
+
+    /// Prepares a connection to the database at a path, creating the tables if needed
+    fn with_connection(path: PathBuf, conn: Connection) -> Result<Self> {
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .and_then(|_| conn.execute_batch(SCHEMA))
+            .map_err(|err| Error::database(&path, err))?;
+        Ok(Self { path, conn })
+    }
+
+    fn error(&self, err: rusqlite::Error) -> Error {
+        Error::database(&self.path, err)
+    }
+
+    fn corrupt(&self, project: &str, reason: String) -> Error {
+        Error::Corrupt {
+            path: self.location(project),
+            reason,
+        }
+    }
+
+    /// Splits a serialized project into its settings and its logs by id and date
+    fn split(&self, project: &str, content: &str) -> Result<(String, Vec<LogRow>)> {
+        let mut value: Value =
+            serde_json::from_str(content).map_err(|err| self.corrupt(project, err.to_string()))?;
+        let logs = match value
+            .as_object_mut()
+            .and_then(|object| object.remove("logs"))
+        {
+            Some(Value::Array(logs)) => logs,
+            _ => return Err(self.corrupt(project, "missing logs".to_owned())),
+        };
+
+        let rows = logs
+            .into_iter()
+            .map(|log| {
+                // Logs are saved as their kind wrapping the fields
+                let fields = log.as_object().and_then(|kinds| kinds.values().next());
+                let id = fields.and_then(|fields| fields.get("id")?.as_i64());
+                let date = fields.and_then(|fields| iso_date(fields.get("date")?));
+                match (id, date) {
+                    (Some(id), Some(date)) => Ok((id, date, log.to_string())),
+                    _ => Err(self.corrupt(project, format!("invalid log {}", log))),
+                }
+            })
+            .collect::<Result<_>>()?;
+        Ok((value.to_string(), rows))
+    }
+}
+
+impl Storage for Sqlite {
+    fn kind(&self) -> StorageKind {
+        StorageKind::Sqlite
+    }
+
+    fn names(&self) -> Result<Vec<String>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT name FROM projects ORDER BY name")
+            .map_err(|err| self.error(err))?;
+        let names = statement
+            .query_map([], |row| row.get(0))
+            .and_then(|rows| rows.collect())
+            .map_err(|err| self.error(err))?;
+        Ok(names)
+    }
+
+    fn load(&self, project: &str) -> Result<Option<String>> {
+        let settings: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT settings FROM projects WHERE name = ?1",
+                [project],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|err| self.error(err))?;
+        let Some(settings) = settings else {
+            return Ok(None);
+        };
+
+        let mut statement = self
+            .conn
+            .prepare("SELECT log FROM logs WHERE project = ?1 ORDER BY id")
+            .map_err(|err| self.error(err))?;
+        let logs: Vec<String> = statement
+            .query_map([project], |row| row.get(0))
+            .and_then(|rows| rows.collect())
+            .map_err(|err| self.error(err))?;
+
+        let mut value: Value = serde_json::from_str(&settings)
+            .map_err(|err| self.corrupt(project, err.to_string()))?;
+        let logs = logs
+            .iter()
+            .map(|log| serde_json::from_str(log))
+            .collect::<std::result::Result<Vec<Value>, _>>()
+            .map_err(|err| self.corrupt(project, err.to_string()))?;
+        let object = value
+            .as_object_mut()
+            .ok_or_else(|| self.corrupt(project, "expected an object".to_owned()))?;
+        object.insert("logs".to_owned(), Value::Array(logs));
+        Ok(Some(value.to_string()))
+    }
+
+    fn store(&self, project: &str, content: &str) -> Result<()> {
+        let (settings, rows) = self.split(project, content)?;
+
+        let transaction = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| self.error(err))?;
+        let saved: BTreeMap<i64, String> = {
+            let mut statement = transaction
+                .prepare("SELECT id, log FROM logs WHERE project = ?1")
+                .map_err(|err| self.error(err))?;
+            let saved = statement
+                .query_map([project], |row| Ok((row.get(0)?, row.get(1)?)))
+                .and_then(|rows| rows.collect())
+                .map_err(|err| self.error(err))?;
+            saved
+        };
+
+        transaction
+            .execute(
+                "INSERT OR REPLACE INTO projects (name, settings) VALUES (?1, ?2)",
+                params![project, settings],
+            )
+            .map_err(|err| self.error(err))?;
+        for id in saved.keys() {
+            if !rows.iter().any(|(row_id, _, _)| row_id == id) {
+                transaction
+                    .execute(
+                        "DELETE FROM logs WHERE project = ?1 AND id = ?2",
+                        params![project, id],
+                    )
+                    .map_err(|err| self.error(err))?;
+            }
+        }
+        for (id, date, log) in &rows {
+            if saved.get(id) != Some(log) {
+                transaction
+                    .execute(
+                        "INSERT OR REPLACE INTO logs (project, id, date, log) VALUES (?1, ?2, ?3, ?4)",
+                        params![project, id, date, log],
+                    )
+                    .map_err(|err| self.error(err))?;
+            }
+        }
+        transaction.commit().map_err(|err| self.error(err))
+    }
+
+    fn remove(&self, project: &str) -> Result<()> {
+        let transaction = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| self.error(err))?;
+        for statement in [
+            "DELETE FROM logs WHERE project = ?1",
+            "DELETE FROM projects WHERE name = ?1",
+        ] {
+            transaction
+                .execute(statement, [project])
+                .map_err(|err| self.error(err))?;
+        }
+        transaction.commit().map_err(|err| self.error(err))
+    }
+
+    fn rename(&self, project: &str, new_name: &str) -> Result<()> {
+        let transaction = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| self.error(err))?;
+        for statement in [
+            "UPDATE projects SET name = ?2 WHERE name = ?1",
+            "UPDATE logs SET project = ?2 WHERE project = ?1",
+            "UPDATE journal SET project = ?2 WHERE project = ?1",
+        ] {
+            transaction
+                .execute(statement, [project, new_name])
+                .map_err(|err| self.error(err))?;
+        }
+        transaction.commit().map_err(|err| self.error(err))
+    }
+
+    fn journal(&self, project: &str) -> Result<Vec<String>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT entry FROM journal WHERE project = ?1 ORDER BY seq")
+            .map_err(|err| self.error(err))?;
+        let entries = statement
+            .query_map([project], |row| row.get(0))
+            .and_then(|rows| rows.collect())
+            .map_err(|err| self.error(err))?;
+        Ok(entries)
+    }
+
+    fn append_journal(&self, project: &str, line: &str) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO journal (project, entry) VALUES (?1, ?2)",
+                [project, line],
+            )
+            .map(|_| ())
+            .map_err(|err| self.error(err))
+    }
+
+    fn journal_len(&self, project: &str) -> Result<usize> {
+        self.conn
+            .query_row(
+                "SELECT COUNT(*) FROM journal WHERE project = ?1",
+                [project],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count as usize)
+            .map_err(|err| self.error(err))
+    }
+
+    fn prune_journal(&self, project: &str, keep: usize) -> Result<()> {
+        self.conn
+            .execute(
+                "DELETE FROM journal WHERE project = ?1 AND seq NOT IN
+                    (SELECT seq FROM journal WHERE project = ?1 ORDER BY seq DESC LIMIT ?2)",
+                params![project, keep as i64],
+            )
+            .map(|_| ())
+            .map_err(|err| self.error(err))
+    }
+
+    fn location(&self, project: &str) -> PathBuf {
+        files::with_suffix(&self.path, &format!(" ({})", project))
+    }
+
+    fn retire(&self) -> Result<PathBuf> {
+        let backup = self.path.with_file_name(RETIRED_FILE_NAME);
+        fs::rename(&self.path, &backup).map_err(|err| Error::io(&self.path, err))?;
+        Ok(backup)
+    }
+}
+
+/// The date of a log as YYYY-MM-DD, so that the dates sort in order
+fn iso_date(date: &Value) -> Option<String> {
+    let part = |name: &str| date.get(name)?.as_u64();
+    Some(format!(
+        "{:04}-{:02}-{:02}",
+        part("year")?,
+        part("month")?,
+        part("day")?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn in_memory() -> Sqlite {
+        let conn = Connection::open_in_memory().unwrap();
+        Sqlite::with_connection(PathBuf::from(":memory:"), conn).unwrap()
+    }
+
+    fn log(id: u64, day: u64, minutes: u64) -> Value {
+        json!({ "Duration": {
+            "id": id,
+            "duration": { "minutes": minutes },
+            "date": { "year": 2024, "month": 3, "day": day },
+            "description": "Work",
+        }})
+    }
+
+    fn project(logs: Vec<Value>) -> String {
+        json!({
+            "version": 2,
+            "name": "work",
+            "next_id": 4,
+            "start_date": { "year": 2024, "month": 3, "day": 1 },
+            "logs": logs,
+        })
+        .to_string()
+    }
+
+    fn load(sqlite: &Sqlite, project: &str) -> Option<Value> {
+        let content = sqlite.load(project).unwrap()?;
+        Some(serde_json::from_str(&content).unwrap())
+    }
+
+    #[test]
+    fn projects_read_back_as_stored() {
+        let sqlite = in_memory();
+        let content = project(vec![log(2, 4, 60), log(1, 5, 90), log(3, 1, 30)]);
+        sqlite.store("work", &content).unwrap();
+        sqlite.store("other", &project(vec![])).unwrap();
+
+        assert_eq!(sqlite.names().unwrap(), vec!["other", "work"]);
+        let mut expected: Value = serde_json::from_str(&content).unwrap();
+        expected["logs"] = json!([log(1, 5, 90), log(2, 4, 60), log(3, 1, 30)]);
+        assert_eq!(load(&sqlite, "work"), Some(expected));
+        assert_eq!(load(&sqlite, "other").unwrap()["logs"], json!([]));
+
+        sqlite.remove("work").unwrap();
+        assert_eq!(load(&sqlite, "work"), None);
+        assert_eq!(sqlite.names().unwrap(), vec!["other"]);
+    }
+
+    #[test]
+    fn only_changed_logs_are_written() {
+        let sqlite = in_memory();
+        sqlite
+            .store(
+                "work",
+                &project(vec![log(1, 4, 60), log(2, 5, 90), log(3, 6, 30)]),
+            )
+            .unwrap();
+        let (settings, _) = sqlite.split("work", &project(vec![])).unwrap();
+
+        // The project row, the edited, the removed and the added log
+        let changes = sqlite.conn.total_changes();
+        let logs = vec![log(1, 4, 60), log(3, 7, 30), log(4, 8, 45)];
+        sqlite.store("work", &project(logs.clone())).unwrap();
+        assert_eq!(sqlite.conn.total_changes() - changes, 4);
+        assert_eq!(load(&sqlite, "work").unwrap()["logs"], json!(logs));
+
+        let changes = sqlite.conn.total_changes();
+        sqlite.store("work", &project(logs)).unwrap();
+        assert_eq!(sqlite.conn.total_changes() - changes, 1);
+
+        let dates: Vec<(i64, String)> = sqlite
+            .conn
+            .prepare("SELECT id, date FROM logs WHERE project = 'work' ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .and_then(|rows| rows.collect())
+            .unwrap();
+        assert_eq!(
+            dates,
+            vec![
+                (1, "2024-03-04".to_owned()),
+                (3, "2024-03-07".to_owned()),
+                (4, "2024-03-08".to_owned())
+            ]
+        );
+        let saved: String = sqlite
+            .conn
+            .query_row(
+                "SELECT settings FROM projects WHERE name = 'work'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(saved, settings);
+    }
+
+    #[test]
+    fn journals_are_kept_per_project() {
+        let sqlite = in_memory();
+        for entry in 1..=5 {
+            sqlite.append_journal("work", &entry.to_string()).unwrap();
+        }
+        sqlite.append_journal("other", "1").unwrap();
+        assert_eq!(sqlite.journal_len("work").unwrap(), 5);
+        assert_eq!(sqlite.journal_len("none").unwrap(), 0);
+
+        sqlite.prune_journal("work", 2).unwrap();
+        assert_eq!(sqlite.journal("work").unwrap(), vec!["4", "5"]);
+        assert_eq!(sqlite.journal("other").unwrap(), vec!["1"]);
+
+        sqlite.store("work", &project(vec![log(1, 4, 60)])).unwrap();
+        sqlite.rename("work", "job").unwrap();
+        assert_eq!(sqlite.journal("job").unwrap(), vec!["4", "5"]);
+        assert_eq!(sqlite.journal_len("work").unwrap(), 0);
+        assert_eq!(
+            load(&sqlite, "job").unwrap()["logs"],
+            json!([log(1, 4, 60)])
+        );
+    }
+}
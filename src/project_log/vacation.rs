@@ -0,0 +1,237 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::time::Date;
+use std::fmt::{Display, Formatter, Result};
+
+/// How many vacation days are given each year, counted from the start of the project
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VacationPolicy {
+    days_per_year: f32,
+    accrual: Accrual,
+    max_carry_over: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Accrual {
+    /// All days are given at the start of the vacation year
+    Yearly,
+    /// A twelfth of the days is given at the start of each month
+    Monthly,
+}
+
+/// The state of the vacation year a date is in
+pub struct VacationBalance {
+    year_start: Date,
+    year_end: Date,
+    allotment: f32,
+    carried_over: f32,
+    earned: f32,
+    taken: f32,
+    planned: f32,
+}
+
+impl VacationPolicy {
+    pub fn new(days_per_year: f32, accrual: Accrual, max_carry_over: f32) -> Self {
+        Self {
+            days_per_year,
+            accrual,
+            max_carry_over,
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        days_per_year: Option<f32>,
+        accrual: Option<Accrual>,
+        max_carry_over: Option<f32>,
+    ) {
+        self.days_per_year = days_per_year.unwrap_or(self.days_per_year);
+        self.accrual = accrual.unwrap_or(self.accrual);
+        self.max_carry_over = max_carry_over.unwrap_or(self.max_carry_over);
+    }
+
+    /// Computes the balance of the vacation year `today` is in, given all vacation
+    /// days (and parts of days) taken or planned in the project.
+    pub fn balance(
+        &self,
+        start_date: &Date,
+        today: &Date,
+        vacation: &[(Date, f32)],
+    ) -> VacationBalance {
+        let days_between = |from: &Date, to: &Date| -> f32 {
+            vacation
+                .iter()
+                .filter(|(date, _)| date >= from && date <= to)
+                .map(|(_, days)| days)
+                .sum()
+        };
+
+        // Roll the unused days over each finished vacation year
+        let mut year = 0;
+        let mut carried_over = 0.0;
+        while &start_date.add_years(year + 1) <= today {
+            let year_end = start_date.add_years(year + 1).add_days(-1);
+            let remaining = carried_over + self.days_per_year
+                - days_between(&start_date.add_years(year), &year_end);
+            carried_over = remaining.min(self.max_carry_over);
+            year += 1;
+        }
+
+        let year_start = start_date.add_years(year);
+        let year_end = start_date.add_years(year + 1).add_days(-1);
+        let earned = match self.accrual {
+            Accrual::Yearly => self.days_per_year,
+            Accrual::Monthly if today < &year_start => 0.0,
+            Accrual::Monthly => {
+                let months = (today.months_since(&year_start) + 1) as f32;
+                self.days_per_year * months / 12.0
+            }
+        };
+
+        VacationBalance {
+            taken: days_between(&year_start, today),
+            planned: days_between(&today.add_days(1), &year_end),
+            year_start,
+            year_end,
+            allotment: self.days_per_year,
+            carried_over,
+            earned,
+        }
+    }
+}
+
+impl VacationBalance {
+    pub fn remaining(&self) -> f32 {
+        self.carried_over + self.earned - self.taken - self.planned
+    }
+}
+
+impl Display for VacationPolicy {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(
+            f,
+            "{} days per year, accrued {}, at most {} days carried over",
+            self.days_per_year,
+            match self.accrual {
+                Accrual::Yearly => "yearly",
+                Accrual::Monthly => "monthly",
+            },
+            self.max_carry_over
+        )
+    }
+}
+
+impl Display for VacationBalance {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        // Only show a decimal when accrual gives parts of days, and never -0 for an
+        // empty sum
+        let days = |days: f32| (days * 10.0).round() / 10.0 + 0.0;
+
+        writeln!(f, "Vacation year: {} to {}", self.year_start, self.year_end)?;
+        writeln!(f, "Carried over: {} days", days(self.carried_over))?;
+        writeln!(
+            f,
+            "Earned: {} of {} days",
+            days(self.earned),
+            days(self.allotment)
+        )?;
+        writeln!(f, "Taken: {} days", days(self.taken))?;
+        writeln!(f, "Planned: {} days", days(self.planned))?;
+        write!(f, "Remaining: {} days", days(self.remaining()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: usize, month: usize, day: usize) -> Date {
+        Date::new(year, month, day)
+    }
+
+    /// Whole vacation days on each of the dates
+    fn days_off(dates: &[Date]) -> Vec<(Date, f32)> {
+        dates.iter().map(|date| (date.clone(), 1.0)).collect()
+    }
+
+    #[test]
+    fn carry_over_is_capped() {
+        let policy = VacationPolicy::new(25.0, Accrual::Yearly, 5.0);
+        let start = date(2022, 1, 1);
+        // 10 days in 2022 and 32 in 2023, 2 more than there were
+        let mut taken: Vec<Date> = date(2022, 7, 10).dates_since(&date(2022, 7, 1)).collect();
+        taken.extend(date(2023, 2, 1).dates_since(&date(2023, 1, 1)));
+        taken.push(date(2024, 1, 10));
+        taken.push(date(2024, 6, 1));
+
+        let balance = policy.balance(&start, &date(2023, 1, 1), &days_off(&taken));
+        assert_eq!(balance.carried_over, 5.0);
+        assert_eq!(balance.taken, 1.0);
+        assert_eq!(balance.planned, 31.0);
+
+        let balance = policy.balance(&start, &date(2024, 3, 1), &days_off(&taken));
+        assert_eq!(balance.year_start, date(2024, 1, 1));
+        assert_eq!(balance.year_end, date(2024, 12, 31));
+        assert_eq!(balance.carried_over, -2.0);
+        assert_eq!(balance.earned, 25.0);
+        assert_eq!(balance.taken, 1.0);
+        assert_eq!(balance.planned, 1.0);
+        assert_eq!(balance.remaining(), 21.0);
+    }
+
+    #[test]
+    fn monthly_accrual() {
+        let policy = VacationPolicy::new(24.0, Accrual::Monthly, 0.0);
+        let start = date(2024, 1, 15);
+        let earned = |today: Date| policy.balance(&start, &today, &[]).earned;
+        assert_eq!(earned(date(2024, 1, 14)), 0.0);
+        assert_eq!(earned(date(2024, 1, 15)), 2.0);
+        assert_eq!(earned(date(2024, 3, 14)), 4.0);
+        assert_eq!(earned(date(2024, 3, 15)), 6.0);
+        assert_eq!(earned(date(2025, 1, 14)), 24.0);
+        assert_eq!(earned(date(2025, 1, 15)), 2.0);
+    }
+
+    #[test]
+    fn partial_first_year() {
+        // The vacation year starts with the project, not in january
+        let policy = VacationPolicy::new(25.0, Accrual::Monthly, 10.0);
+        let start = date(2023, 7, 1);
+        let taken = [(date(2023, 12, 27), 3.0), (date(2023, 12, 28), 0.5)];
+
+        let balance = policy.balance(&start, &date(2023, 12, 31), &taken);
+        assert_eq!(balance.year_start, start);
+        assert_eq!(balance.year_end, date(2024, 6, 30));
+        assert_eq!(balance.earned, 12.5);
+        assert_eq!(balance.remaining(), 9.0);
+
+        let balance = policy.balance(&start, &date(2024, 7, 1), &taken);
+        assert_eq!(balance.year_start, date(2024, 7, 1));
+        assert_eq!(balance.carried_over, 10.0);
+        assert_eq!(balance.remaining(), 10.0 + 25.0 / 12.0);
+    }
+
+    #[test]
+    fn starting_on_february_29th() {
+        let policy = VacationPolicy::new(20.0, Accrual::Yearly, 20.0);
+        let start = date(2024, 2, 29);
+        let taken = days_off(&[date(2025, 2, 27), date(2025, 2, 28)]);
+
+        let balance = policy.balance(&start, &date(2025, 2, 27), &taken);
+        assert_eq!(balance.year_start, start);
+        assert_eq!(balance.year_end, date(2025, 2, 27));
+        assert_eq!(balance.taken, 1.0);
+        assert_eq!(balance.planned, 0.0);
+
+        let balance = policy.balance(&start, &date(2025, 2, 28), &taken);
+        assert_eq!(balance.year_start, date(2025, 2, 28));
+        assert_eq!(balance.carried_over, 19.0);
+        assert_eq!(balance.taken, 1.0);
+        assert_eq!(balance.remaining(), 38.0);
+
+        let balance = policy.balance(&start, &date(2028, 3, 1), &taken);
+        assert_eq!(balance.year_start, date(2028, 2, 29));
+        assert_eq!(balance.year_end, date(2029, 2, 27));
+    }
+}
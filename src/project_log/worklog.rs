@@ -1,36 +1,120 @@
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
-use crate::time::{Date, Duration, Period};
-use std::fmt::{Display, Formatter, Result};
+use crate::error::{Error, Result};
+use crate::time::{Date, Duration, Period, TimeZone};
+use std::fmt::{self, Display, Formatter};
 
 // TODO: Change to trait and have PeriodWorkLog and TimeWorkLog maybe?
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PeriodLog {
-    duration: Duration, // Just derived from period, can be removed
+    #[serde(default)]
+    id: usize,
     period: Period,
-    breaks: Vec<Duration>,
+    breaks: Vec<Break>,
     date: Date,
     description: Option<String>,
+    /// Zone the times were logged in, if not the local one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timezone: Option<TimeZone>,
+}
+
+/// A break is either just a length, or the actual interval it was taken
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Break {
+    Duration(Duration),
+    Period(Period),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DurationLog {
+    #[serde(default)]
+    id: usize,
     duration: Duration,
     date: Date,
     description: Option<String>,
 }
 
+/// A day (or half day) away from work
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AbsenceLog {
+    #[serde(default)]
+    id: usize,
+    kind: Absence,
+    date: Date,
+    half_day: bool,
+    description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Absence {
+    Vacation,
+    Sick,
+    Parental,
+    /// Time off paid with flex time, so the day is still expected to be worked
+    Comp,
+}
+
+/// Changes to make to a log, where None leaves that part as it is
+pub struct LogEdit {
+    pub duration: Option<Duration>,
+    pub period: Option<Period>,
+    pub date: Option<Date>,
+    pub breaks: Option<Vec<Break>>,
+    pub description: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum WorkLog {
     Period(PeriodLog),
     Duration(DurationLog),
+    Absence(AbsenceLog),
 }
 
 impl WorkLog {
+    /// Identifier unique within the project, or 0 if not yet added to one
+    pub fn get_id(&self) -> usize {
+        match self {
+            WorkLog::Period(period_log) => period_log.id,
+            WorkLog::Duration(duration_log) => duration_log.id,
+            WorkLog::Absence(absence_log) => absence_log.id,
+        }
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        match self {
+            WorkLog::Period(period_log) => period_log.id = id,
+            WorkLog::Duration(duration_log) => duration_log.id = id,
+            WorkLog::Absence(absence_log) => absence_log.id = id,
+        }
+    }
+
     pub fn get_duration(&self) -> Duration {
         match self {
-            WorkLog::Period(period_log) => period_log.duration.clone(),
+            WorkLog::Period(period_log) => period_log.duration(),
             WorkLog::Duration(duration_log) => duration_log.duration.clone(),
+            WorkLog::Absence(_) => Duration::from_m(0),
+        }
+    }
+
+    /// The worked time on each date the log covers, where a period past midnight
+    /// spreads its breaks evenly over the time worked each day
+    pub fn get_duration_per_date(&self) -> Vec<(Date, Duration)> {
+        match self {
+            WorkLog::Period(period_log) => period_log.duration_per_date(),
+            _ => vec![(self.get_date(), self.get_duration())],
+        }
+    }
+
+    /// The period worked and the total length of its breaks, for period logs
+    pub fn get_period(&self) -> Option<(&Period, Duration)> {
+        match self {
+            WorkLog::Period(period_log) => Some((
+                &period_log.period,
+                period_log.breaks.iter().map(Break::duration).sum(),
+            )),
+            _ => None,
         }
     }
 
@@ -38,40 +122,265 @@ impl WorkLog {
         match self {
             WorkLog::Period(period_log) => period_log.date.clone(),
             WorkLog::Duration(duration_log) => duration_log.date.clone(),
+            WorkLog::Absence(absence_log) => absence_log.date.clone(),
+        }
+    }
+
+    /// Number of vacation days the log uses up
+    pub fn get_vacation_days(&self) -> f32 {
+        match self {
+            WorkLog::Absence(AbsenceLog {
+                kind: Absence::Vacation,
+                half_day,
+                ..
+            }) => {
+                if *half_day {
+                    0.5
+                } else {
+                    1.0
+                }
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// How much of the expected hours of the day the log excuses
+    pub fn get_excused(&self, expected: &Duration) -> Duration {
+        match self {
+            WorkLog::Absence(absence_log) => absence_log.excused(expected),
+            _ => Duration::from_m(0),
+        }
+    }
+
+    /// Applies the changes, turning the log into a period or duration log if
+    /// a period or duration is given, and recomputing the worked duration.
+    pub fn edit(self, edit: LogEdit) -> Result<WorkLog> {
+        let id = self.get_id();
+        let date = edit.date.unwrap_or(self.get_date());
+        let has_breaks = edit
+            .breaks
+            .as_ref()
+            .is_some_and(|breaks| !breaks.is_empty());
+
+        let mut edited = match self {
+            WorkLog::Absence(mut absence_log) => {
+                if edit.duration.is_some() || edit.period.is_some() || edit.breaks.is_some() {
+                    return Err(Error::InvalidInput(
+                        "Only the date and description of an absence can be edited".to_owned(),
+                    ));
+                }
+                absence_log.date = date;
+                absence_log.description = edit.description.or(absence_log.description);
+                WorkLog::Absence(absence_log)
+            }
+            WorkLog::Period(period_log) if edit.duration.is_none() => {
+                let mut new_log = PeriodLog::new(
+                    edit.period.unwrap_or(period_log.period),
+                    date,
+                    String::new(),
+                    edit.breaks.unwrap_or(period_log.breaks),
+                    period_log.timezone,
+                );
+                new_log.description = edit.description.or(period_log.description);
+                WorkLog::Period(new_log)
+            }
+            WorkLog::Duration(_) if edit.period.is_none() && has_breaks => {
+                return Err(Error::InvalidInput(
+                    "Only logs with a period can have breaks".to_owned(),
+                ));
+            }
+            WorkLog::Duration(duration_log) if edit.period.is_none() => {
+                let mut new_log = DurationLog::new(
+                    edit.duration.unwrap_or(duration_log.duration),
+                    date,
+                    String::new(),
+                );
+                new_log.description = edit.description.or(duration_log.description);
+                WorkLog::Duration(new_log)
+            }
+            other => {
+                // Changing between a period and a duration log
+                let description = edit
+                    .description
+                    .or_else(|| other.get_description().cloned())
+                    .unwrap_or("Work".to_owned());
+                match (edit.period, edit.duration) {
+                    (Some(period), _) => WorkLog::new_period(
+                        period,
+                        date,
+                        description,
+                        edit.breaks.unwrap_or_default(),
+                        None,
+                    ),
+                    (_, Some(_)) if has_breaks => {
+                        return Err(Error::InvalidInput(
+                            "Only logs with a period can have breaks".to_owned(),
+                        ))
+                    }
+                    (_, Some(duration)) => WorkLog::new_duration(duration, date, description),
+                    _ => unreachable!(
+                        "Period and duration logs are only converted when given either"
+                    ),
+                }
+            }
+        };
+
+        edited.set_id(id);
+        Ok(edited)
+    }
+
+    /// Whether the logs are of the same work, such as when imported twice, regardless
+    /// of their descriptions
+    pub fn same_work(&self, other: &WorkLog) -> bool {
+        self.get_date() == other.get_date()
+            && match (self, other) {
+                (WorkLog::Period(a), WorkLog::Period(b)) => {
+                    a.period.start() == b.period.start() && a.period.end() == b.period.end()
+                }
+                (WorkLog::Duration(a), WorkLog::Duration(b)) => a.duration == b.duration,
+                (WorkLog::Absence(a), WorkLog::Absence(b)) => a.kind == b.kind,
+                _ => false,
+            }
+    }
+
+    /// The description, where work without one is described as work
+    pub fn get_label(&self) -> String {
+        match self {
+            WorkLog::Absence(absence_log) => absence_log.description.clone().unwrap_or_default(),
+            _ => self.get_description().cloned().unwrap_or("Work".to_owned()),
+        }
+    }
+
+    /// The kind of absence along with if it was only half the day, for absence logs
+    pub fn get_absence(&self) -> Option<(Absence, bool)> {
+        match self {
+            WorkLog::Absence(absence_log) => Some((absence_log.kind, absence_log.half_day)),
+            _ => None,
+        }
+    }
+
+    fn get_description(&self) -> Option<&String> {
+        match self {
+            WorkLog::Period(period_log) => period_log.description.as_ref(),
+            WorkLog::Duration(duration_log) => duration_log.description.as_ref(),
+            WorkLog::Absence(absence_log) => absence_log.description.as_ref(),
         }
     }
 
-    pub fn new_period(period: Period, date: Date, desc: String, breaks: Vec<Duration>) -> WorkLog {
-        WorkLog::Period(PeriodLog::new(period, date, desc, breaks))
+    /// A log of a period in a timezone, or the local one if None
+    pub fn new_period(
+        period: Period,
+        date: Date,
+        desc: String,
+        breaks: Vec<Break>,
+        timezone: Option<TimeZone>,
+    ) -> WorkLog {
+        WorkLog::Period(PeriodLog::new(period, date, desc, breaks, timezone))
     }
 
     pub fn new_duration(duration: Duration, date: Date, desc: String) -> WorkLog {
         WorkLog::Duration(DurationLog::new(duration, date, desc))
     }
+
+    pub fn new_absence(kind: Absence, date: Date, half_day: bool, desc: Option<String>) -> WorkLog {
+        WorkLog::Absence(AbsenceLog {
+            id: 0,
+            kind,
+            date,
+            half_day,
+            description: desc,
+        })
+    }
 }
 
 impl PeriodLog {
-    pub fn new(period: Period, date: Date, description: String, breaks: Vec<Duration>) -> Self {
-        let duration = period.duration() - breaks.clone().into_iter().sum(); // TODO: Don't clone
+    pub fn new(
+        period: Period,
+        date: Date,
+        description: String,
+        breaks: Vec<Break>,
+        timezone: Option<TimeZone>,
+    ) -> Self {
+        let zone = timezone.clone().unwrap_or(TimeZone::Local);
+        let breaks: Vec<Break> = breaks
+            .into_iter()
+            .map(|pause| match pause {
+                Break::Period(pause) => {
+                    // Breaks starting before the period are taken the day after
+                    let days = (pause.start() < period.start()) as i64;
+                    Break::Period(pause.localize(&date.add_days(days), &zone))
+                }
+                other => other,
+            })
+            .collect();
+        let period = period.localize(&date, &zone);
 
         Self {
-            duration,
+            id: 0,
             period,
             breaks,
             date,
             description: Some(description),
+            timezone,
+        }
+    }
+
+    /// Time worked, which is the period except for the breaks
+    fn duration(&self) -> Duration {
+        self.period.duration() - self.breaks.iter().map(Break::duration).sum()
+    }
+
+    fn duration_per_date(&self) -> Vec<(Date, Duration)> {
+        let per_day = self.period.duration_per_day();
+        let total = self.period.duration().to_m() as i64;
+        let worked = self.duration().to_m() as i64;
+
+        let mut remaining = worked;
+        let mut durations = vec![];
+        for (days, day) in per_day.iter().enumerate() {
+            let minutes = if days + 1 == per_day.len() || total == 0 {
+                remaining
+            } else {
+                day.to_m() as i64 * worked / total
+            };
+            remaining -= minutes;
+            durations.push((
+                self.date.add_days(days as i64),
+                Duration::from_m(minutes as i32),
+            ));
         }
+        durations
     }
 
     #[allow(dead_code)]
-    fn date<'a>(&'a self) -> &'a Date {
+    fn date(&self) -> &Date {
         &self.date
     }
 }
 
+impl AbsenceLog {
+    fn excused(&self, expected: &Duration) -> Duration {
+        match (self.kind, self.half_day) {
+            (Absence::Comp, _) => Duration::from_m(0),
+            (_, true) => expected.clone() / 2,
+            (_, false) => expected.clone(),
+        }
+    }
+}
+
+impl Break {
+    pub fn duration(&self) -> Duration {
+        match self {
+            Break::Duration(duration) => duration.clone(),
+            Break::Period(period) => period.duration(),
+        }
+    }
+}
+
 impl DurationLog {
     pub fn new(duration: Duration, date: Date, description: String) -> Self {
         Self {
+            id: 0,
             duration,
             date,
             description: Some(description),
@@ -80,26 +389,28 @@ impl DurationLog {
 }
 
 impl Display for WorkLog {
-    fn fmt(&self, f: &mut Formatter) -> Result {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
             WorkLog::Period(period_log) => period_log.fmt(f),
             WorkLog::Duration(duration_log) => duration_log.fmt(f),
+            WorkLog::Absence(absence_log) => absence_log.fmt(f),
         }
     }
 }
 
 impl Display for PeriodLog {
-    fn fmt(&self, f: &mut Formatter) -> Result {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}, {}", self.date, self.duration(), self.period)?;
+        if let Some(timezone) = &self.timezone {
+            write!(f, " ({})", timezone)?;
+        }
         write!(
             f,
-            "{}: {}, {}, {}",
-            self.date,
-            self.duration,
-            self.period,
+            ", {}",
             self.description.clone().unwrap_or("Work".to_owned())
         )?;
 
-        if self.breaks.len() > 0 {
+        if !self.breaks.is_empty() {
             write!(f, " | Breaks: {}", self.breaks[0])?;
             for dur in &self.breaks[1..] {
                 write!(f, ", {}", dur)?;
@@ -109,14 +420,48 @@ impl Display for PeriodLog {
     }
 }
 
+impl Display for Break {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Break::Duration(duration) => duration.fmt(f),
+            Break::Period(period) => write!(f, "{} ({})", period, period.duration()),
+        }
+    }
+}
+
 impl Display for DurationLog {
-    fn fmt(&self, f: &mut Formatter) -> Result {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(
             f,
-            "{}: {}, {}\n",
+            "{}: {}, {}",
             self.date,
             self.duration,
             &self.description.clone().unwrap_or("Work".to_owned())
         )
     }
 }
+
+impl Display for AbsenceLog {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.date, self.kind)?;
+        if self.half_day {
+            write!(f, " (half day)")?;
+        }
+        if let Some(description) = &self.description {
+            write!(f, ", {}", description)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Absence {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            Absence::Vacation => "Vacation",
+            Absence::Sick => "Sick leave",
+            Absence::Parental => "Parental leave",
+            Absence::Comp => "Comp time",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -1,28 +1,57 @@
 // Would probably be MUCH better, but less fun to use the chrono crate
-use chrono::{self, Datelike};
+use chrono::{self, Datelike, Offset, Timelike};
 use lazy_static::lazy_static;
 use regex;
 use serde::{Deserialize, Serialize};
-use std::cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd};
+use std::cmp::{Eq, Ord, PartialEq, PartialOrd};
 use std::fmt::{Display, Formatter, Result};
 use std::iter::Sum;
-use std::ops::{Add, Sub};
+use std::ops::{Add, Div, Sub};
+use std::str::FromStr;
+
+/// Names of the days of the week, indexed by `Date::weekday`, which are shortened to
+/// their first three letters when shown
+pub const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Ord)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Time {
     hours: usize,
     minutes: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Ord, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct Duration {
     minutes: i32,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// A stretch of time which ends `days` midnights after it starts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Period {
     from: Time,
     to: Time,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    days: usize,
+    /// Minutes ahead of UTC at the start and end, differing over daylight saving changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offsets: Option<(i32, i32)>,
+}
+
+/// Where the times of a log are from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub enum TimeZone {
+    Local,
+    Named(chrono_tz::Tz),
+    /// Minutes ahead of UTC
+    Fixed(i32),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -32,6 +61,9 @@ pub struct Date {
     day: usize,
 }
 
+/// The latest year a date can have, as years are written with 4 digits
+const MAX_YEAR: i32 = 9999;
+
 // Was a hassle to implement, so I did it with chrono, but this means it should stay private :/
 struct DateIterator {
     current: chrono::NaiveDate,
@@ -53,27 +85,107 @@ impl Date {
         trunkated
     }
 
-    pub fn weekdays_since(&self, from: &Date) -> usize {
-        // Returns a vector over all dates from self until to.
-        DateIterator::weekdays_since(from, self)
+    pub fn end_of_month(&self) -> Date {
+        let (year, month) = if self.month == 12 {
+            (self.year + 1, 1)
+        } else {
+            (self.year, self.month + 1)
+        };
+        Date::new(year, month, 1).add_days(-1)
     }
 
-    fn to_naive_chrono(&self) -> chrono::NaiveDate {
-        chrono::NaiveDate::from_ymd(self.year as i32, self.month as u32, self.day as u32)
+    pub fn is_valid(&self) -> bool {
+        (1..=MAX_YEAR as usize).contains(&self.year)
+            && chrono::NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)
+                .is_some()
+    }
+
+    pub fn add_days(&self, days: i64) -> Date {
+        Date::from_naive_chrono(&(self.to_naive_chrono() + chrono::Duration::days(days)))
+    }
+
+    /// The date some days later, unless that is before year 1 or after year 9999
+    pub fn checked_add_days(&self, days: i64) -> Option<Date> {
+        let date = self
+            .to_naive_chrono()
+            .checked_add_signed(chrono::Duration::try_days(days)?)?;
+        Date::from_naive_checked(&date)
+    }
+
+    /// The same date some years later, moving february 29th to the 28th when needed
+    pub fn add_years(&self, years: usize) -> Date {
+        let later = Date::new(self.year + years, self.month, self.day);
+        if later.is_valid() {
+            later
+        } else {
+            Date::new(later.year, later.month, later.day - 1)
+        }
     }
-}
 
-impl DateIterator {
-    fn weekdays_since(from: &Date, to: &Date) -> usize {
+    /// Number of whole months from `from` until self
+    pub fn months_since(&self, from: &Date) -> usize {
+        let months = (self.year * 12 + self.month) - (from.year * 12 + from.month);
+        if self.day < from.day {
+            months.saturating_sub(1)
+        } else {
+            months
+        }
+    }
+
+    /// Day of the week, where monday is 0 and sunday is 6
+    pub fn weekday(&self) -> usize {
+        self.to_naive_chrono().weekday().num_days_from_monday() as usize
+    }
+
+    /// The date of a weekday (0 is monday) in an ISO 8601 week, if the week exists
+    pub fn from_iso_week(year: usize, week: usize, weekday: usize) -> Option<Date> {
+        if weekday >= 7 {
+            return None;
+        }
+        let monday =
+            chrono::NaiveDate::from_isoywd_opt(year as i32, week as u32, chrono::Weekday::Mon)?;
+        Date::from_naive_checked(&(monday + chrono::Duration::days(weekday as i64)))
+    }
+
+    /// The ISO 8601 week-numbering year and week of the date
+    pub fn iso_week(&self) -> (usize, usize) {
+        let week = self.to_naive_chrono().iso_week();
+        (week.year() as usize, week.week() as usize)
+    }
+
+    /// Number of days from `from` until self, negative if `from` is later
+    pub fn days_since(&self, from: &Date) -> i64 {
+        self.to_naive_chrono()
+            .signed_duration_since(from.to_naive_chrono())
+            .num_days()
+    }
+
+    /// Iterates over all dates from `from` until self, both inclusive
+    pub fn dates_since(&self, from: &Date) -> impl Iterator<Item = Date> {
         DateIterator {
             current: from.to_naive_chrono(),
-            end: to.to_naive_chrono(),
+            end: self.to_naive_chrono(),
         }
-        .into_iter()
-        .filter(|date| {
-            date.weekday() != chrono::Weekday::Sat && date.weekday() != chrono::Weekday::Sun
-        })
-        .count()
+        .map(|date| Date::from_naive_chrono(&date))
+    }
+
+    fn to_naive_chrono(&self) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)
+            .expect("Dates should be validated when parsed")
+    }
+
+    fn from_naive_chrono(date: &chrono::NaiveDate) -> Date {
+        Date::new(
+            date.year() as usize,
+            date.month() as usize,
+            date.day() as usize,
+        )
+    }
+
+    fn from_naive_checked(date: &chrono::NaiveDate) -> Option<Date> {
+        (1..=MAX_YEAR)
+            .contains(&date.year())
+            .then(|| Date::from_naive_chrono(date))
     }
 }
 
@@ -83,8 +195,8 @@ impl Iterator for DateIterator {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.current <= self.end {
-            let current = self.current.clone();
-            self.current = self.current.succ();
+            let current = self.current;
+            self.current = self.current.succ_opt()?;
             Some(current)
         } else {
             None
@@ -97,8 +209,16 @@ impl Time {
         Self { hours, minutes }
     }
 
+    /// The time as a chrono time the same day, or at midnight the next day for 24:00
+    fn to_naive_chrono(&self, date: &Date) -> chrono::NaiveDateTime {
+        date.to_naive_chrono()
+            .and_hms_opt(0, 0, 0)
+            .expect("Midnight always exists")
+            + chrono::Duration::minutes((self.hours * 60 + self.minutes) as i64)
+    }
+
     // TODO: Change to just implementing Sub?
-    // TODO: can work not just within one day!
+    /// Time from an earlier time the same day, negative if it is actually later
     pub fn time_since(&self, earlier: &Self) -> Duration {
         let hours_diff = self.hours as i32 - earlier.hours as i32;
         let minutes_diff = self.minutes as i32 - earlier.minutes as i32;
@@ -106,17 +226,6 @@ impl Time {
     }
 }
 
-//NOTE: Could just derive as it is lexical
-impl PartialOrd for Time {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let ord = self
-            .hours
-            .cmp(&other.hours)
-            .then(self.minutes.cmp(&other.minutes));
-        Some(ord)
-    }
-}
-
 impl Display for Time {
     fn fmt(&self, f: &mut Formatter) -> Result {
         write!(f, "{:02}:{:02}", self.hours, self.minutes)
@@ -125,7 +234,31 @@ impl Display for Time {
 
 impl Display for Period {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "{}-{}", self.from, self.to)
+        write!(f, "{}-{}", self.from, self.to)?;
+        if self.days > 0 {
+            write!(f, "+{}", self.days)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for TimeZone {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            TimeZone::Local => write!(f, "local"),
+            TimeZone::Named(tz) => write!(f, "{}", tz.name()),
+            TimeZone::Fixed(0) => write!(f, "UTC"),
+            TimeZone::Fixed(offset) => {
+                let sign = if *offset < 0 { '-' } else { '+' };
+                write!(
+                    f,
+                    "UTC{}{:02}:{:02}",
+                    sign,
+                    offset.abs() / 60,
+                    offset.abs() % 60
+                )
+            }
+        }
     }
 }
 
@@ -141,7 +274,9 @@ impl Display for Duration {
             }
             Ok(())
         };
-        if hrs != 0 && min != 0 {
+        if hrs == 0 && min == 0 {
+            write!(f, "0 minutes")?;
+        } else if hrs != 0 && min != 0 {
             write_time(f, hrs, "hour")?;
             write!(f, " and ")?;
             write_time(f, min, "minute")?;
@@ -159,12 +294,6 @@ impl Display for Date {
     }
 }
 
-impl PartialOrd for Duration {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.minutes.cmp(&other.minutes))
-    }
-}
-
 // NOTE: Maybe should do this for the borrowed type instead?
 impl Sub<Duration> for Duration {
     type Output = Duration;
@@ -187,6 +316,16 @@ impl Add<Duration> for Duration {
     }
 }
 
+impl Div<i32> for Duration {
+    type Output = Duration;
+
+    fn div(self, divisor: i32) -> Self::Output {
+        Duration {
+            minutes: self.minutes / divisor,
+        }
+    }
+}
+
 impl Sum for Duration {
     fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
         iter.fold(Duration::from_m(0), |acc, dur| acc + dur)
@@ -194,12 +333,177 @@ impl Sum for Duration {
 }
 
 impl Period {
+    /// A period within a day, or ending the next day if `to` is before `from`
     pub fn new(from: Time, to: Time) -> Self {
-        Self { from, to }
+        let days = (to < from) as usize;
+        Self::spanning(from, to, days)
+    }
+
+    /// A period ending a number of days after it starts
+    pub fn spanning(from: Time, to: Time, days: usize) -> Self {
+        Self {
+            from,
+            to,
+            days,
+            offsets: None,
+        }
     }
 
+    pub fn start(&self) -> Time {
+        self.from.clone()
+    }
+
+    /// The end time along with how many midnights after the start it is
+    pub fn end(&self) -> (Time, usize) {
+        (self.to.clone(), self.days)
+    }
+
+    /// Remembers the UTC offsets of the zone at the start and end of the period,
+    /// when starting on the date
+    pub fn localize(self, date: &Date, zone: &TimeZone) -> Self {
+        let from = zone.offset(&self.from.to_naive_chrono(date));
+        let to = zone.offset(&self.to.to_naive_chrono(&date.add_days(self.days as i64)));
+        Self {
+            offsets: Some((from, to)),
+            ..self
+        }
+    }
+
+    /// The actual time passed, where daylight saving changes make some nights shorter or longer
     pub fn duration(&self) -> Duration {
-        self.to.time_since(&self.from)
+        Duration::from_hm(24 * self.days as i32, 0) + self.to.time_since(&self.from)
+            - self.offset_change()
+    }
+
+    /// The time spent on each day of the period, starting with the day it begins.
+    /// Any daylight saving change is counted on the last day.
+    pub fn duration_per_day(&self) -> Vec<Duration> {
+        if self.days == 0 {
+            return vec![self.duration()];
+        }
+
+        let midnight = Time::new(24, 0);
+        let mut durations = vec![midnight.time_since(&self.from)];
+        durations.extend((1..self.days).map(|_| Duration::from_hm(24, 0)));
+        durations.push(self.to.time_since(&Time::new(0, 0)) - self.offset_change());
+        durations
+    }
+
+    /// Start and end as minutes since 1970 in UTC, when starting on the date, or none
+    /// for periods logged before offsets were kept.
+    pub fn interval(&self, date: &Date) -> Option<(i64, i64)> {
+        let (from_offset, _) = self.offsets?;
+        let (start, _) = self.wall_clock_interval(date);
+        let start = start - from_offset as i64;
+        Some((start, start + self.duration().minutes as i64))
+    }
+
+    /// Start and end as minutes since 1970 as read on the clock, when starting on the date
+    pub fn wall_clock_interval(&self, date: &Date) -> (i64, i64) {
+        let midnight = date.days_since(&Date::new(1970, 1, 1)) * 24 * 60;
+        let minutes = |time: &Time| (time.hours * 60 + time.minutes) as i64;
+        (
+            midnight + minutes(&self.from),
+            midnight + self.days as i64 * 24 * 60 + minutes(&self.to),
+        )
+    }
+
+    fn offset_change(&self) -> Duration {
+        let (from, to) = self.offsets.unwrap_or((0, 0));
+        Duration::from_m(to - from)
+    }
+}
+
+impl TimeZone {
+    /// The current date and time in the zone
+    pub fn now(&self) -> (Date, Time) {
+        let utc = chrono::Utc::now().naive_utc();
+        let local = match self {
+            TimeZone::Local => chrono::Local::now().naive_local(),
+            _ => utc + chrono::Duration::minutes(self.offset_from_utc(&utc) as i64),
+        };
+
+        (
+            Date::from_naive_chrono(&local.date()),
+            Time::new(local.hour() as usize, local.minute() as usize),
+        )
+    }
+
+    /// Minutes ahead of UTC at a local time. Times skipped when moving the clock forward
+    /// use the offset from before, and times repeated when moving it back the first one.
+    fn offset(&self, local: &chrono::NaiveDateTime) -> i32 {
+        match self {
+            TimeZone::Local => offset_at(&chrono::Local, local),
+            TimeZone::Named(tz) => offset_at(tz, local),
+            TimeZone::Fixed(offset) => *offset,
+        }
+    }
+
+    fn offset_from_utc(&self, utc: &chrono::NaiveDateTime) -> i32 {
+        use chrono::TimeZone as _;
+        match self {
+            TimeZone::Local => chrono::Local.offset_from_utc_datetime(utc).fix(),
+            TimeZone::Named(tz) => tz.offset_from_utc_datetime(utc).fix(),
+            TimeZone::Fixed(offset) => return *offset,
+        }
+        .local_minus_utc()
+            / 60
+    }
+}
+
+fn offset_at<Tz: chrono::TimeZone>(tz: &Tz, local: &chrono::NaiveDateTime) -> i32 {
+    match tz.offset_from_local_datetime(local) {
+        chrono::LocalResult::Single(offset) | chrono::LocalResult::Ambiguous(offset, _) => {
+            offset.fix().local_minus_utc() / 60
+        }
+        chrono::LocalResult::None => offset_at(tz, &(*local - chrono::Duration::hours(3))),
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = String;
+
+    /// Parses `local`, an offset such as `UTC+2` or `-05:30`, or a name such as `Europe/Stockholm`
+    fn from_str(zone_str: &str) -> std::result::Result<Self, Self::Err> {
+        lazy_static! {
+            static ref RE: regex::Regex =
+                regex::Regex::new(r"^(?i:utc|gmt)?(?:([+-])(\d\d?)(?::?(\d\d))?)?$").unwrap();
+        }
+
+        if zone_str.eq_ignore_ascii_case("local") {
+            return Ok(TimeZone::Local);
+        }
+        if let Some(caps) = RE.captures(zone_str).filter(|_| !zone_str.is_empty()) {
+            let hours: i32 = caps.get(2).map_or(0, |cap| cap.as_str().parse().unwrap());
+            let minutes: i32 = caps.get(3).map_or(0, |cap| cap.as_str().parse().unwrap());
+            let sign = if caps.get(1).is_some_and(|sign| sign.as_str() == "-") {
+                -1
+            } else {
+                1
+            };
+            if hours <= 14 && minutes < 60 {
+                return Ok(TimeZone::Fixed(sign * (hours * 60 + minutes)));
+            }
+        }
+
+        zone_str
+            .parse()
+            .map(TimeZone::Named)
+            .map_err(|_| format!("Unknown timezone '{}'", zone_str))
+    }
+}
+
+impl TryFrom<String> for TimeZone {
+    type Error = String;
+
+    fn try_from(zone_str: String) -> std::result::Result<Self, Self::Error> {
+        zone_str.parse()
+    }
+}
+
+impl From<TimeZone> for String {
+    fn from(zone: TimeZone) -> Self {
+        zone.to_string()
     }
 }
 
@@ -217,40 +521,72 @@ impl Duration {
     pub fn to_hm(&self) -> (i32, i32) {
         (self.minutes / 60, self.minutes % 60)
     }
+
+    pub fn to_m(&self) -> i32 {
+        self.minutes
+    }
+}
+
+fn is_zero(days: &usize) -> bool {
+    *days == 0
 }
 
 pub fn now() -> (Date, Time) {
-    lazy_static! {
-        static ref RE: regex::Regex = regex::Regex::new(concat!(
-            r"^(?P<year>\d{4})-",
-            r"(?P<month>\d{2})-",
-            r"(?P<day>\d{2})T",
-            r"(?P<hour>\d{2}):",
-            r"(?P<min>\d{2})"
-        ))
-        .unwrap();
-    }
-
-    let chrono_now = chrono::offset::Local::now().to_rfc3339();
-
-    let caps = RE.captures(&chrono_now).expect("Wrong date regex!\n");
-
-    let date = Date::new(
-        cap_to_usize(caps.name("year")),
-        cap_to_usize(caps.name("month")),
-        cap_to_usize(caps.name("day")),
-    );
-    let time = Time::new(
-        cap_to_usize(caps.name("hour")),
-        cap_to_usize(caps.name("min")),
-    );
-
-    (date, time)
+    TimeZone::Local.now()
 }
 
-fn cap_to_usize(cap: Option<regex::Match>) -> usize {
-    cap.unwrap()
-        .as_str()
-        .parse::<usize>()
-        .expect("Time/Date should be usize")
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stockholm() -> TimeZone {
+        "Europe/Stockholm".parse().unwrap()
+    }
+
+    #[test]
+    fn period_duration_over_daylight_saving_changes() {
+        let night = Period::new(Time::new(22, 0), Time::new(6, 0));
+        assert_eq!(night.duration(), Duration::from_hm(8, 0));
+
+        // Clocks moved forward at 02:00 on 2024-03-31 and back at 03:00 on 2024-10-27
+        let spring = night
+            .clone()
+            .localize(&Date::new(2024, 3, 30), &stockholm());
+        assert_eq!(spring.duration(), Duration::from_hm(7, 0));
+        assert_eq!(
+            spring.duration_per_day(),
+            vec![Duration::from_hm(2, 0), Duration::from_hm(5, 0)]
+        );
+
+        let autumn = night
+            .clone()
+            .localize(&Date::new(2024, 10, 26), &stockholm());
+        assert_eq!(autumn.duration(), Duration::from_hm(9, 0));
+
+        let summer = night.localize(&Date::new(2024, 6, 1), &stockholm());
+        assert_eq!(summer.duration(), Duration::from_hm(8, 0));
+    }
+
+    #[test]
+    fn period_duration_over_several_days() {
+        let shift = Period::spanning(Time::new(8, 0), Time::new(10, 0), 2)
+            .localize(&Date::new(2024, 10, 25), &stockholm());
+        assert_eq!(shift.duration(), Duration::from_hm(51, 0));
+    }
+
+    #[test]
+    fn interval_is_in_utc_and_wall_clock_interval_is_not() {
+        let date = Date::new(2024, 6, 3);
+        let legacy = Period::new(Time::new(8, 0), Time::new(12, 0));
+        let local = legacy.clone().localize(&date, &stockholm());
+
+        assert_eq!(legacy.interval(&date), None);
+        let (start, end) = local.interval(&date).unwrap();
+        assert_eq!(end - start, 4 * 60);
+        assert_eq!(local.wall_clock_interval(&date), (start + 120, end + 120));
+        assert_eq!(
+            legacy.wall_clock_interval(&date),
+            local.wall_clock_interval(&date)
+        );
+    }
 }